@@ -2,6 +2,7 @@ import ctypes
 import pdb
 import os
 import sys
+import time
 from ctypes import (
     CDLL,
     POINTER,
@@ -150,6 +151,36 @@ def new_server() -> c_int64:
     handle = c_uint64(handle)
     return handle
 
+def new_server_from_seed(seed) -> c_int64:
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_new_server_from_seed")
+    lib_fn.restype = c_uint64
+
+    handle = lib_fn(_encode_bytes(seed), byref(err))
+    if handle == 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    handle = c_uint64(handle)
+    return handle
+
+def new_server_from_keys(witness_secret_key, sign_secret_key, revoked_secret_key, accumulator) -> c_int64:
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_new_server_from_keys")
+    lib_fn.restype = c_uint64
+
+    handle = lib_fn(
+        _encode_bytes(witness_secret_key),
+        _encode_bytes(sign_secret_key),
+        _encode_bytes(revoked_secret_key),
+        _encode_bytes(accumulator),
+        byref(err),
+    )
+    if handle == 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    handle = c_uint64(handle)
+    return handle
+
 
 def new_user(server) -> c_int64:
     buffer = FfiByteBuffer()
@@ -253,12 +284,88 @@ def user_create_witness(server, user):
     buffer = _decode_bytes(buffer)
     return buffer
 
-def user_make_membership_proof(server, user) -> c_int64:
+def user_make_reissue_request(user):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_make_reissue_request")
+    lib_fn(_encode_bytes(user), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_reissue_witness(server, request):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_reissue_witness")
+    lib_fn(server, _encode_bytes(request), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_apply_reissued_witness(user, reissued):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_apply_reissued_witness")
+    lib_fn(_encode_bytes(user), _encode_bytes(reissued), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def server_rotate_keys(server, grace_until):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_rotate_keys")
+    lib_fn(server, c_uint64(grace_until), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_make_migration_request(user):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_make_migration_request")
+    lib_fn(_encode_bytes(user), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_migrate_witness(server, request):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_migrate_witness")
+    lib_fn(server, _encode_bytes(request), c_uint64(int(time.time())), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_apply_migrated_witness(user, migrated):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_apply_migrated_witness")
+    lib_fn(_encode_bytes(user), _encode_bytes(migrated), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_make_membership_proof(server, user, challenge) -> c_int64:
     buffer = FfiByteBuffer()
     err = FfiError()
-    challenge = bytearray(os.urandom(32))
     lib_fn = _get_func("allosaurus_user_make_membership_proof")
-    lib_fn(server, _encode_bytes(user), _encode_bytes(challenge), byref(buffer), byref(err))
+    lib_fn(server, _encode_bytes(user), _encode_bytes(bytearray(challenge)), byref(buffer), byref(err))
     if err.code != 0:
         message = string_at(err.message)
         raise Exception(message)
@@ -274,6 +381,171 @@ def witness_check_membership_proof(server, proof) -> c_int64:
         raise Exception(message)
     return "Membership proof verified successfully"
 
+def verifier_check_membership_proof(session, server, proof, now) -> c_int64:
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_verifier_check_membership_proof")
+    lib_fn(session, server, _encode_bytes(proof), c_uint64(now), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    return "Membership proof verified successfully"
+
+def user_make_epoch_membership_proof(server, user, challenge):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_make_epoch_membership_proof")
+    lib_fn(server, _encode_bytes(user), _encode_bytes(bytearray(challenge)), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def server_publish_accumulator_history(server, window):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_publish_accumulator_history")
+    lib_fn(server, c_uint64(window), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def verifier_check_epoch_membership_proof(session, public_keys, history, proof, window, now):
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_verifier_check_epoch_membership_proof")
+    lib_fn(session, _encode_bytes(public_keys), _encode_bytes(history), _encode_bytes(proof), c_uint64(window), c_uint64(now), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    return "Membership proof verified successfully"
+
+def new_verifier_session(verifier_id, ttl, capacity) -> c_int64:
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_new_verifier_session")
+    lib_fn.restype = c_uint64
+
+    handle = lib_fn(_encode_bytes(verifier_id), c_uint64(ttl), c_uint64(capacity), byref(err))
+    if handle == 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    handle = c_uint64(handle)
+    return handle
+
+def verifier_issue_challenge(session, now):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_verifier_issue_challenge")
+    lib_fn(session, c_uint64(now), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def server_publish(server, registry_id):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_publish")
+    lib_fn(server, _encode_bytes(registry_id.encode("utf-8")), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_make_non_revocation_presentation(server, user, registry_id, challenge):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_make_non_revocation_presentation")
+    lib_fn(
+        server,
+        _encode_bytes(user),
+        _encode_bytes(registry_id.encode("utf-8")),
+        _encode_bytes(bytearray(challenge)),
+        byref(buffer),
+        byref(err),
+    )
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def check_non_revocation_presentation(session, presentation, publication, now):
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_check_non_revocation_presentation")
+    lib_fn(session, _encode_bytes(presentation), _encode_bytes(publication), c_uint64(now), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    return "Presentation verified successfully"
+
+def user_create_non_membership_witness(server, user):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_create_non_membership_witness")
+    lib_fn(server, _encode_bytes(user), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def server_publish_revoked_set_updates(server, epoch):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_publish_revoked_set_updates")
+    lib_fn(server, c_uint64(epoch), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def server_prune(server, retain_epochs):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_prune")
+    lib_fn(server, c_uint64(retain_epochs), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_apply_revoked_set_updates(user, updates):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_apply_revoked_set_updates")
+    lib_fn(_encode_bytes(user), _encode_bytes(updates), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def user_make_non_membership_proof(user, challenge):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_user_make_non_membership_proof")
+    lib_fn(_encode_bytes(user), _encode_bytes(bytearray(challenge)), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def server_check_non_membership_proof(session, server, proof, now):
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_check_non_membership_proof")
+    lib_fn(session, server, _encode_bytes(proof), c_uint64(now), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    return "Non-membership proof verified successfully"
+
 def check_witness(user):
     lib_fn = _get_func("allosaurus_user_check_witness")
     err = lib_fn(_encode_bytes(user))
@@ -291,7 +563,7 @@ def server_batch_delete(server, user_list):
 
     buffer = FfiByteBuffer()
     err = FfiError()
-    lib_fn = _get_func("allosaurus_server_update")
+    lib_fn = _get_func("allosaurus_server_batch_delete")
     lib_fn(user_buffer, len(user_list), server, byref(buffer), byref(err))
     if err.code != 0:
         message = string_at(err.message)
@@ -340,4 +612,67 @@ def witness_multi_batch_update(witness, y, deletions, coefficients) -> c_int64:
         message = string_at(err.message)
         raise Exception(message)
     buffer = _decode_bytes(buffer)
-    return buffer
\ No newline at end of file
+    return buffer
+
+def server_publish_split_updates(server, epoch, slice_size):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_server_publish_split_updates")
+    lib_fn(server, c_uint64(epoch), c_uint64(slice_size), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def new_witness_service(publication, retain_epochs) -> c_int64:
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_new_witness_service")
+    lib_fn.restype = c_uint64
+
+    handle = lib_fn(_encode_bytes(publication), c_uint64(retain_epochs), byref(err))
+    if handle == 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    handle = c_uint64(handle)
+    return handle
+
+def witness_service_get_epoch(service) -> int:
+    epoch = c_uint64()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_witness_service_get_epoch")
+    lib_fn(service, byref(epoch), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    return epoch.value
+
+def witness_service_register(service, user_id, witness, epoch):
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_witness_service_register")
+    lib_fn(service, _encode_bytes(user_id), _encode_bytes(witness), c_uint64(epoch), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+
+def witness_service_ingest(service, updates):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_witness_service_ingest")
+    lib_fn(service, _encode_bytes(updates), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
+
+def witness_service_get_witness(service, user_id):
+    buffer = FfiByteBuffer()
+    err = FfiError()
+    lib_fn = _get_func("allosaurus_witness_service_get_witness")
+    lib_fn(service, _encode_bytes(user_id), byref(buffer), byref(err))
+    if err.code != 0:
+        message = string_at(err.message)
+        raise Exception(message)
+    buffer = _decode_bytes(buffer)
+    return buffer
@@ -0,0 +1,48 @@
+from hashlib import shake_128
+
+# Reference implementation of `UserID::from_credential_id` in agora-allosaurus-rs.
+# Holders, issuers and witness servers written in python can use this to derive
+# the same user ID as the rust library without going through the FFI.
+
+# BLS12-381 scalar field modulus
+Q = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001
+SALT = b"VB-ACC-HASH-SALT-"
+CREDENTIAL_ID_DST = b"ALLOSAUR-USERID-FROM-CREDENTIAL-ID-V1"
+
+
+def _length_prefixed(data: bytes) -> bytes:
+    return len(data).to_bytes(4, byteorder='big') + data
+
+
+def user_id_from_credential_id(registry_id: str, cred_rev_id: str) -> bytes:
+    """Returns the 32-byte big-endian user ID for a credential."""
+    shake = shake_128()
+    shake.update(SALT)
+    shake.update(CREDENTIAL_ID_DST)
+    shake.update(_length_prefixed(registry_id.encode('utf-8')))
+    shake.update(_length_prefixed(cred_rev_id.encode('utf-8')))
+    # Scalar::from_bytes_wide reduces a 512-bit little-endian integer
+    scalar = int.from_bytes(shake.digest(64), byteorder='little') % Q
+    return scalar.to_bytes(32, byteorder='big')
+
+
+# Known answer tests shared with the rust test suite
+TEST_VECTORS = [
+    ("", "0", "5676bea2a9f747611f0fcbb0ed90ffced2ddad7993e0042270cd1ae1e11b329b"),
+    (
+        "Th7MpTaRZVRYnPiabds81Y:4:Th7MpTaRZVRYnPiabds81Y:3:CL:185:default:CL_ACCUM:TAG1",
+        "1",
+        "21fe3b2f3dc7dd4fc4472d8e82009514ddca6c15da41a039c1566ffc6978017c",
+    ),
+    (
+        "Th7MpTaRZVRYnPiabds81Y:4:Th7MpTaRZVRYnPiabds81Y:3:CL:185:default:CL_ACCUM:TAG1",
+        "100000",
+        "1db9751d300b7d7a10bc1b9035255ba6215b7f70e0cbaf02bb3a7a2c47ad0a87",
+    ),
+    ("did:example:registry/rev_reg/1", "42", "0daa6fe9dfec1b778f3978c65936262b988080dfff2693a21883356e55c8c87f"),
+]
+
+if __name__ == "__main__":
+    for registry_id, cred_rev_id, expected in TEST_VECTORS:
+        assert user_id_from_credential_id(registry_id, cred_rev_id).hex() == expected
+    print("ok")
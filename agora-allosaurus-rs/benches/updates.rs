@@ -1,5 +1,5 @@
 use agora_allosaurus_rs::accumulator::{
-    Accumulator, Coefficient, Element, MembershipWitness, PublicKey, SecretKey,
+    Accumulator, Coefficient, Element, MembershipWitness, PolynomialG1, PublicKey, SecretKey,
 };
 use blsful::inner_types::*;
 use rayon::prelude::*;
@@ -10,7 +10,7 @@ use uint_zigzag::Uint;
 
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use agora_allosaurus_rs::{AccParams, PublicKeys, Server, User, UserID, UserUpdate};
+use agora_allosaurus_rs::{AccParams, Checkpoint, PublicKeys, Server, User, UserID, UserUpdate};
 
 //-------BENCHMARK PARAMETERS ------//
 
@@ -36,7 +36,7 @@ const NUM_SAMPLES: usize = 50; // the number of samples for each benchmark
 
 criterion_group!(name = benches;
     config = Criterion::default().sample_size(NUM_SAMPLES);
-    targets = allosaur_update, single_server, batch_update
+    targets = allosaur_update, single_server, batch_update, multi_scalar_mul
 );
 criterion_main!(benches);
 
@@ -110,6 +110,59 @@ fn single_server(c: &mut Criterion) {
     }
 }
 
+// Compares one-at-a-time scalar multiplications with the multi-scalar
+// multiplication used to evaluate point polynomials and server updates
+fn multi_scalar_mul(c: &mut Criterion) {
+    c.benchmark_group("multi_scalar_mul");
+
+    for num_dels in ALLOSAUR_CHANGES {
+        println!("=================================================");
+        println!(
+            "=Multi-scalar multiplication Benchmark with {} points=",
+            num_dels
+        );
+        println!("=================================================");
+
+        let points: Vec<G1Projective> = (0..num_dels)
+            .map(|_| G1Projective::GENERATOR * Element::random().0)
+            .collect();
+        let scalars: Vec<Scalar> = (0..num_dels).map(|_| Element::random().0).collect();
+        let poly = PolynomialG1(points.clone());
+        let x = Element::random().0;
+
+        c.bench_function(
+            "Point polynomial evaluation, one multiplication per point",
+            |b| {
+                b.iter(|| {
+                    let mut p = x;
+                    let mut res = points[0];
+                    for point in &points[1..] {
+                        res += point * p;
+                        p *= x;
+                    }
+                    res
+                })
+            },
+        );
+        c.bench_function(
+            "Point polynomial evaluation, multi-scalar multiplication",
+            |b| b.iter(|| poly.evaluate(x)),
+        );
+
+        c.bench_function("Inner product, one multiplication per point", |b| {
+            b.iter(|| {
+                points
+                    .iter()
+                    .zip(scalars.iter())
+                    .fold(G1Projective::IDENTITY, |acc, (p, s)| acc + p * s)
+            })
+        });
+        c.bench_function("Inner product, multi-scalar multiplication", |b| {
+            b.iter(|| G1Projective::sum_of_products(&points, &scalars))
+        });
+    }
+}
+
 // Batch update protocol of Vitto and Biryukov 2020 (https://eprint.iacr.org/2020/777)
 fn batch_update(c: &mut Criterion) {
     c.benchmark_group("batch_update");
@@ -205,6 +258,10 @@ fn allosaur_update(c: &mut Criterion) {
             .collect();
         let all_users: HashSet<UserID> = users.iter().map(|u| u.id).collect();
 
+        // The revoked set has its own key and starts out empty
+        let revoked_secret_key = SecretKey::new(None);
+        let revoked_accumulator = Accumulator::with_elements(&revoked_secret_key, &[]);
+
         // Generates an array of servers
         // Here each server has the full accumulator secret key;
         // this is necessary for our fast and lazy delete to run the benchmark
@@ -212,12 +269,18 @@ fn allosaur_update(c: &mut Criterion) {
         let mut servers: Vec<Server> = (0..SHARES)
             .map(|_| Server {
                 accumulators: vec![accumulator],
+                checkpoint: Checkpoint::genesis(accumulator),
                 witness_secret_key: alpha.clone(),
                 public_keys,
                 sign_secret_key: s.clone(),
                 all_users: all_users.clone(),
                 all_witnesses: all_witnesses.clone(),
                 deletions: Vec::new(),
+                pruned_deletions: Vec::new(),
+                revoked_secret_key: revoked_secret_key.clone(),
+                revoked_accumulators: vec![revoked_accumulator],
+                retired_keys: None,
+                update_tables: Default::default(),
             })
             .collect();
 
@@ -257,14 +320,23 @@ fn allosaur_update(c: &mut Criterion) {
             serde_bare::to_vec(&user_server_message).unwrap().len() * SHARES
         );
 
-        // Benchmark the server side, for only one server
+        // Benchmark the server side, for only one server, for the first
+        // holder to request these chunks
+        c.bench_function("ALLOSAUR server-side update, cold cache", |b| {
+            b.iter(|| {
+                servers[0].update_tables.clear();
+                servers[0].update(user_d, &user_shares[0]).unwrap()
+            })
+        });
+
+        // Benchmark the server side once other holders requested the same chunks
         c.bench_function("ALLOSAUR server-side update ", |b| {
-            b.iter(|| servers[0].update(user_d, &user_shares[0]))
+            b.iter(|| servers[0].update(user_d, &user_shares[0]).unwrap())
         });
 
         // Actually get the server responses, from all servers
         let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..SHARES)
-            .map(|i| servers[i].update(user_d, &user_shares[i]))
+            .map(|i| servers[i].update(user_d, &user_shares[i]).unwrap())
             .collect();
 
         // Get the length of data sent back to the user
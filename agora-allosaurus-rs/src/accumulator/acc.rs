@@ -1,11 +1,11 @@
-use super::utils::hash_to_g1;
+use super::utils::{fixed_base_mul, hash_to_g1};
 use super::{
     utils::{generate_fr, SALT},
     SecretKey,
 };
 use blsful::inner_types::*;
 use core::fmt::{self, Display, Formatter};
-use rand::RngCore;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
@@ -69,7 +69,12 @@ impl Element {
 
     /// Construct a random element
     pub fn random() -> Self {
-        Self(generate_fr(SALT, None, rand::rngs::OsRng))
+        Self::random_with_rng(rand::rngs::OsRng)
+    }
+
+    /// Construct a random element from `rng`
+    pub fn random_with_rng(rng: impl RngCore + CryptoRng) -> Self {
+        Self(generate_fr(SALT, None, rng))
     }
 }
 
@@ -164,8 +169,13 @@ impl Accumulator {
 
     /// Create a random accumulator
     pub fn random() -> Self {
+        Self::random_with_rng(rand::rngs::OsRng)
+    }
+
+    /// Create a random accumulator from `rng`
+    pub fn random_with_rng(mut rng: impl RngCore + CryptoRng) -> Self {
         let mut buffer = [0u8; 64];
-        rand::rngs::OsRng.fill_bytes(&mut buffer);
+        rng.fill_bytes(&mut buffer);
         Self(hash_to_g1(buffer))
     }
 
@@ -247,10 +257,14 @@ impl Accumulator {
         let d = key.batch_deletions(deletions);
 
         a.0 *= d.0;
-        let coefficients = key
+        let scalars: Vec<Scalar> = key
             .create_coefficients(additions, deletions)
             .iter()
-            .map(|c| Coefficient(self.0 * c.0))
+            .map(|c| c.0)
+            .collect();
+        let coefficients = fixed_base_mul(self.0, &scalars)
+            .into_iter()
+            .map(Coefficient)
             .collect();
         self.0 *= a.0;
         coefficients
@@ -1,18 +1,47 @@
 use super::{
-    utils::{generate_fr, Polynomial},
+    utils::{generate_fr, join, weighted_prefix_products, weighted_suffix_products, Polynomial},
     Element,
 };
 use blsful::inner_types::*;
 use core::convert::TryFrom;
+use rand::{CryptoRng, RngCore};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use zeroize::DefaultIsZeroes;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// The salt for generating secret keys
+// Giuseppe Vitto, Alex Biryukov = VB
+// Accumulator = ACC
+const KEYGEN_SALT: &[u8] = b"VB-ACC-KEYGEN-SALT-";
 
 /// Represents \alpha (secret key) on page 6 in
 /// <https://eprint.iacr.org/2020/777.pdf>
-#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+///
+/// The key is zeroized when dropped, so it is not `Copy`.
+/// Use `clone` where a second copy is really needed.
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct SecretKey(pub Scalar);
 
-impl DefaultIsZeroes for SecretKey {}
+impl core::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "SecretKey {{ <redacted> }}")
+    }
+}
+
+impl Zeroize for SecretKey {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SecretKey {}
 
 impl From<SecretKey> for [u8; 32] {
     fn from(s: SecretKey) -> Self {
@@ -35,9 +64,12 @@ impl SecretKey {
 
     /// Create a new secret key
     pub fn new(seed: Option<&[u8]>) -> Self {
-        // Giuseppe Vitto, Alex Biryukov = VB
-        // Accumulator = ACC
-        Self(generate_fr(b"VB-ACC-KEYGEN-SALT-", seed, rand::rngs::OsRng))
+        Self(generate_fr(KEYGEN_SALT, seed, rand::rngs::OsRng))
+    }
+
+    /// Create a new random secret key from `rng`
+    pub fn random_with_rng(rng: impl RngCore + CryptoRng) -> Self {
+        Self(generate_fr(KEYGEN_SALT, None, rng))
     }
 
     /// Return the raw byte representation of the key
@@ -51,12 +83,17 @@ impl SecretKey {
 
     /// Compute the batch add elements value
     pub fn batch_additions(&self, additions: &[Element]) -> Element {
-        Element(
-            additions
-                .iter()
-                .map(|v| v.0 + self.0)
-                .fold(Scalar::ONE, |a, y| a * y),
-        )
+        #[cfg(feature = "parallel")]
+        let product = additions
+            .par_iter()
+            .map(|v| v.0 + self.0)
+            .reduce(|| Scalar::ONE, |a, y| a * y);
+        #[cfg(not(feature = "parallel"))]
+        let product = additions
+            .iter()
+            .map(|v| v.0 + self.0)
+            .fold(Scalar::ONE, |a, y| a * y);
+        Element(product)
     }
 
     /// Compute the batch remove elements value
@@ -70,48 +107,47 @@ impl SecretKey {
         additions: &[Element],
         deletions: &[Element],
     ) -> Vec<Element> {
+        let (v_d, mut v_a) = join(
+            || self.deletion_coefficients(additions, deletions),
+            || self.addition_coefficients(additions),
+        );
+        // vA - vD
+        v_a -= v_d;
+
+        v_a.0.iter().map(|b| Element(*b)).collect()
+    }
+
+    /// vD(x) * ∏ 1..n (yA_i + alpha)
+    fn deletion_coefficients(&self, additions: &[Element], deletions: &[Element]) -> Polynomial {
         // vD(x) = ∑^{m}_{s=1}{ ∏ 1..s {yD_i + alpha}^-1 ∏ 1 ..s-1 {yD_j - x}
-        let one = Scalar::ONE;
-        let m1 = -one;
-        let mut v_d = Polynomial::with_capacity(deletions.len());
-        for s in 0..deletions.len() {
-            // ∏ 1..s (yD_i + alpha)^-1
-            let c = self.batch_deletions(&deletions[0..s + 1]).0;
-            let mut poly = Polynomial::with_capacity(deletions.len());
-            poly.push(one);
-            // ∏ 1..(s-1) (yD_j - x)
-            for j in deletions.iter().take(s) {
-                poly *= &[j.0, m1];
-            }
-            poly *= c;
-            v_d += poly;
+        let deleted: Vec<Scalar> = deletions.iter().map(|d| d.0).collect();
+        // ∏ 1..s (yD_i + alpha)^-1 for every s, using one inversion
+        let mut weights: Vec<Scalar> = deleted.iter().map(|d| d + self.0).collect();
+        let mut inv = self.batch_deletions(deletions).0;
+        for w in weights.iter_mut().rev() {
+            let factor = *w;
+            *w = inv;
+            inv *= factor;
         }
+        let mut v_d = weighted_prefix_products(&deleted, &weights);
 
         //v_d(x) * ∏ 1..n (yA_i + alpha)
         v_d *= self.batch_additions(additions).0;
+        v_d
+    }
 
+    /// vA(x)
+    fn addition_coefficients(&self, additions: &[Element]) -> Polynomial {
         // vA(x) = ∑^n_{s=1}{ ∏ 1..s-1 {yA_i + alpha} ∏ s+1..n {yA_j - x} }
-        let mut v_a = Polynomial::with_capacity(additions.len());
-        for s in 0..additions.len() {
-            // ∏ 1..s-1 {yA_i + alpha}
-            let c = if s == 0 {
-                one
-            } else {
-                self.batch_additions(&additions[0..s]).0
-            };
-            let mut poly = Polynomial::with_capacity(additions.len());
-            poly.push(one);
-            // ∏ s+1..n {yA_j - x}
-            for j in additions.iter().skip(s + 1) {
-                poly *= &[j.0, m1];
-            }
-            poly *= c;
-            v_a += poly;
+        let added: Vec<Scalar> = additions.iter().map(|a| a.0).collect();
+        // ∏ 1..s-1 {yA_i + alpha}
+        let mut weights = Vec::with_capacity(added.len());
+        let mut c = Scalar::ONE;
+        for a in &added {
+            weights.push(c);
+            c *= a + self.0;
         }
-        // vA - vD
-        v_a -= v_d;
-
-        v_a.0.iter().map(|b| Element(*b)).collect()
+        weighted_suffix_products(&added, &weights)
     }
 }
 
@@ -194,4 +230,48 @@ mod tests {
         let coefficients = key.create_coefficients(&data[0..2], &data[2..5]);
         assert_eq!(coefficients.len(), 3);
     }
+
+    #[test]
+    fn coefficient_schoolbook_test() {
+        let key = SecretKey::new(None);
+        let additions: Vec<Element> = (0..100).map(|_| Element::random()).collect();
+        let deletions: Vec<Element> = (0..90).map(|_| Element::random()).collect();
+        let m1 = -Scalar::ONE;
+
+        // The definitions of vD and vA, one term at a time
+        let mut v_d = Polynomial::default();
+        for s in 0..deletions.len() {
+            let mut poly = Polynomial(vec![key.batch_deletions(&deletions[0..s + 1]).0]);
+            for j in deletions.iter().take(s) {
+                poly *= &[j.0, m1];
+            }
+            v_d += poly;
+        }
+        v_d *= key.batch_additions(&additions).0;
+        let mut v_a = Polynomial::default();
+        for s in 0..additions.len() {
+            let mut poly = Polynomial(vec![key.batch_additions(&additions[0..s]).0]);
+            for j in additions.iter().skip(s + 1) {
+                poly *= &[j.0, m1];
+            }
+            v_a += poly;
+        }
+        v_a -= v_d;
+
+        let coefficients = key.create_coefficients(&additions, &deletions);
+        assert_eq!(coefficients.len(), v_a.0.len());
+        assert!(coefficients
+            .iter()
+            .zip(v_a.0.iter())
+            .all(|(c, v)| c.0 == *v));
+        assert!(key.create_coefficients(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn zeroize_test() {
+        let mut key = SecretKey::new(None);
+        assert_eq!(format!("{:?}", key), "SecretKey { <redacted> }");
+        key.zeroize();
+        assert_eq!(key.0, Scalar::ZERO);
+    }
 }
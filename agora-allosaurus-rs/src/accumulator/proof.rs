@@ -1,10 +1,11 @@
 use super::{
-    utils::{generate_fr, hash_to_g1, SALT},
+    utils::{ct_sum_of_products, generate_fr, hash_to_g1, SALT},
     Accumulator, Element, MembershipWitness, NonMembershipWitness, ProofMessage, PublicKey,
 };
 use crate::utils::{g1, sc};
 use blsful::inner_types::*;
 use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
 
@@ -102,12 +103,22 @@ impl MembershipProofCommitting {
         witness: MembershipWitness,
         proof_params: ProofParams,
         pubkey: PublicKey,
+    ) -> Self {
+        Self::new_with_rng(y, witness, proof_params, pubkey, rand::rngs::OsRng)
+    }
+
+    /// Like `new`, drawing the blinding factors from `rng`
+    pub fn new_with_rng(
+        y: ProofMessage,
+        witness: MembershipWitness,
+        proof_params: ProofParams,
+        pubkey: PublicKey,
+        mut rng: impl RngCore + CryptoRng,
     ) -> Self {
         let message = y.get_message();
-        let rng = rand::rngs::OsRng;
         // Randomly select σ, ρ
-        let sigma = generate_fr(SALT, None, rng);
-        let rho = generate_fr(SALT, None, rng);
+        let sigma = generate_fr(SALT, None, &mut rng);
+        let rho = generate_fr(SALT, None, &mut rng);
 
         // E_C = C + (σ + ρ)Z
         let e_c = proof_params.z * (sigma + rho) + witness.0;
@@ -127,11 +138,11 @@ impl MembershipProofCommitting {
         // Randomly pick r_σ,r_ρ,r_δσ,r_δρ
         // r_y is either generated randomly or supplied in case this proof is used to
         // bind to an external proof
-        let r_y = y.get_blinder(rng);
-        let r_sigma = generate_fr(SALT, None, rng);
-        let r_rho = generate_fr(SALT, None, rng);
-        let r_delta_sigma = generate_fr(SALT, None, rng);
-        let r_delta_rho = generate_fr(SALT, None, rng);
+        let r_y = y.get_blinder(&mut rng);
+        let r_sigma = generate_fr(SALT, None, &mut rng);
+        let r_rho = generate_fr(SALT, None, &mut rng);
+        let r_delta_sigma = generate_fr(SALT, None, &mut rng);
+        let r_delta_rho = generate_fr(SALT, None, &mut rng);
 
         // R_σ = r_σ X
         let cap_r_sigma = proof_params.x * r_sigma;
@@ -429,12 +440,30 @@ impl NonMembershipProofCommitting {
         pubkey: PublicKey,
         blinding_factor: Option<Element>,
     ) -> Self {
-        let rng = rand::rngs::OsRng;
+        Self::new_with_rng(
+            y,
+            witness,
+            proof_params,
+            pubkey,
+            blinding_factor,
+            rand::rngs::OsRng,
+        )
+    }
+
+    /// Like `new`, drawing the blinding factors from `rng`
+    pub fn new_with_rng(
+        y: Element,
+        witness: NonMembershipWitness,
+        proof_params: ProofParams,
+        pubkey: PublicKey,
+        blinding_factor: Option<Element>,
+        mut rng: impl RngCore + CryptoRng,
+    ) -> Self {
         // Randomly pick r_σ,r_ρ,r_δσ,r_δρ
 
         // Randomly select σ, ρ
-        let sigma = generate_fr(SALT, None, rng);
-        let rho = generate_fr(SALT, None, rng);
+        let sigma = generate_fr(SALT, None, &mut rng);
+        let rho = generate_fr(SALT, None, &mut rng);
 
         // E_C = C + (σ + ρ)Z
         let e_c = proof_params.z * (sigma + rho) + witness.c;
@@ -456,11 +485,11 @@ impl NonMembershipProofCommitting {
         // bind to an external proof
         let r_y = blinding_factor
             .map(|bf| bf.0)
-            .unwrap_or_else(|| generate_fr(SALT, None, rng));
-        let r_sigma = generate_fr(SALT, None, rng);
-        let r_rho = generate_fr(SALT, None, rng);
-        let r_delta_sigma = generate_fr(SALT, None, rng);
-        let r_delta_rho = generate_fr(SALT, None, rng);
+            .unwrap_or_else(|| generate_fr(SALT, None, &mut rng));
+        let r_sigma = generate_fr(SALT, None, &mut rng);
+        let r_rho = generate_fr(SALT, None, &mut rng);
+        let r_delta_sigma = generate_fr(SALT, None, &mut rng);
+        let r_delta_rho = generate_fr(SALT, None, &mut rng);
 
         // R_σ = r_σ X
         let cap_r_sigma = proof_params.x * r_sigma;
@@ -475,8 +504,8 @@ impl NonMembershipProofCommitting {
         let cap_r_delta_rho = cap_r(&[t_rho, -proof_params.y], &[r_y, r_delta_rho]);
 
         // Randomly pick \tau, \pi
-        let tau = generate_fr(SALT, None, rng);
-        let pi = generate_fr(SALT, None, rng);
+        let tau = generate_fr(SALT, None, &mut rng);
+        let pi = generate_fr(SALT, None, &mut rng);
 
         // E_d = d P + \tau K
         let e_d = cap_r(
@@ -485,15 +514,16 @@ impl NonMembershipProofCommitting {
         );
 
         // E_{d^{-1}} = d^{-1}P + \pi K
+        // d is never zero for a valid witness, a proof with a zero d fails to verify
         let e_dm1 = cap_r(
             &[G1Projective::GENERATOR, proof_params.k],
-            &[witness.d.invert().unwrap(), pi],
+            &[witness.d.invert().unwrap_or(Scalar::ZERO), pi],
         );
 
         // Randomly pick r_u,r_v,r_w
-        let r_u = generate_fr(SALT, None, rng);
-        let r_v = generate_fr(SALT, None, rng);
-        let r_w = generate_fr(SALT, None, rng);
+        let r_u = generate_fr(SALT, None, &mut rng);
+        let r_v = generate_fr(SALT, None, &mut rng);
+        let r_w = generate_fr(SALT, None, &mut rng);
 
         // R_A = r_u P + r_v K
         let cap_r_a = cap_r(&[G1Projective::GENERATOR, proof_params.k], &[r_u, r_v]);
@@ -613,7 +643,7 @@ impl NonMembershipProofCommitting {
 }
 
 /// A ZKP non-membership proof
-#[derive(Debug, Default, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize)]
 pub struct NonMembershipProof {
     e_c: G1Projective,
     e_d: G1Projective,
@@ -631,6 +661,11 @@ pub struct NonMembershipProof {
 }
 
 impl NonMembershipProof {
+    /// The response for the hidden value y
+    pub fn get_s_y(&self) -> Scalar {
+        self.s_y
+    }
+
     /// Generate the structure that can be used in the challenge hash
     /// returns a struct to avoid recomputing
     pub fn finalize(
@@ -781,8 +816,9 @@ impl NonMembershipProofFinal {
     }
 }
 
+/// The scalars are blinding factors or witness values, so the sum is constant-time
 fn cap_r(bases: &[G1Projective], scalars: &[Scalar]) -> G1Projective {
-    G1Projective::sum_of_products(bases, scalars)
+    ct_sum_of_products(bases, scalars)
 }
 
 pub(crate) fn pair(g1: G1Projective, g2: G2Projective) -> Gt {
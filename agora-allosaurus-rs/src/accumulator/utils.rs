@@ -1,11 +1,13 @@
 use super::Element;
-use blsful::inner_types::{ExpandMsgXmd, G1Projective, Scalar};
+use blsful::inner_types::{ExpandMsgXmd, Field, G1Projective, Group, PrimeField, Scalar};
 use rand::{CryptoRng, RngCore};
 use sha2::Sha256;
 use sha3::{
     digest::{ExtendableOutput, Update, XofReader},
     Shake128,
 };
+use subtle::{ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroize;
 
 /// Similar to https://tools.ietf.org/html/draft-irtf-cfrg-bls-signature-04#section-2.3
 /// info is left blank
@@ -78,17 +80,107 @@ impl PolynomialG1 {
             return None;
         }
 
-        let mut p = x;
-        let mut res = self.0[0];
-
-        for i in 1..self.0.len() {
-            res += self.0[i] * p;
+        let mut powers = Vec::with_capacity(self.0.len());
+        let mut p = Scalar::ONE;
+        for _ in 0..self.0.len() {
+            powers.push(p);
             p *= x;
         }
+        // x is usually a user ID
+        let res = ct_sum_of_products(&self.0, &powers);
+        powers.zeroize();
         Some(res)
     }
 }
 
+/// The number of scalar bits handled by each table of [`fixed_base_mul`]
+/// and [`ct_sum_of_products`]
+const FIXED_BASE_WINDOW: usize = 4;
+
+/// Computes `∑ scalars[i] * points[i]` in time independent of the scalars.
+///
+/// `G1Projective::sum_of_products` skips zero digits and picks buckets by
+/// digit, so its running time and memory accesses depend on the scalars.
+/// Here every point gets a table of its first 16 multiples, and every 4-bit
+/// window adds one entry per point, found by scanning the whole table with
+/// a constant-time select.
+pub(crate) fn ct_sum_of_products(points: &[G1Projective], scalars: &[Scalar]) -> G1Projective {
+    const DIGITS: usize = 1 << FIXED_BASE_WINDOW;
+    const WINDOWS: usize = 256 / FIXED_BASE_WINDOW;
+
+    let tables: Vec<[G1Projective; DIGITS]> = points
+        .iter()
+        .map(|point| {
+            let mut row = [G1Projective::IDENTITY; DIGITS];
+            for j in 1..DIGITS {
+                row[j] = row[j - 1] + point;
+            }
+            row
+        })
+        .collect();
+    let mut bytes: Vec<[u8; 32]> = scalars.iter().map(|s| s.to_le_bytes()).collect();
+
+    let mut res = G1Projective::IDENTITY;
+    for i in (0..WINDOWS).rev() {
+        for _ in 0..FIXED_BASE_WINDOW {
+            res = res.double();
+        }
+        for (row, bytes) in tables.iter().zip(bytes.iter()) {
+            let digit = (bytes[i / 2] >> ((i % 2) * FIXED_BASE_WINDOW)) & 0x0F;
+            let mut entry = G1Projective::IDENTITY;
+            for (j, point) in row.iter().enumerate() {
+                entry.conditional_assign(point, (j as u8).ct_eq(&digit));
+            }
+            res += entry;
+        }
+    }
+    bytes.zeroize();
+    res
+}
+
+/// Multiplies one base point by many scalars.
+///
+/// Precomputes `j * 2^(4i) * base` for every 4-bit window `i` and digit `j`,
+/// so each product costs one addition per window instead of a full
+/// double-and-add. Every lookup scans the whole row of its window with a
+/// constant-time select, so the scalars may be secret.
+pub(crate) fn fixed_base_mul(base: G1Projective, scalars: &[Scalar]) -> Vec<G1Projective> {
+    const DIGITS: usize = 1 << FIXED_BASE_WINDOW;
+    const WINDOWS: usize = 256 / FIXED_BASE_WINDOW;
+    // Building the table costs about as much as a dozen plain multiplications
+    if scalars.len() < 16 {
+        return scalars.iter().map(|s| base * s).collect();
+    }
+
+    let mut table = Vec::with_capacity(WINDOWS);
+    let mut window_base = base;
+    for _ in 0..WINDOWS {
+        let mut row = [G1Projective::IDENTITY; DIGITS];
+        for j in 1..DIGITS {
+            row[j] = row[j - 1] + window_base;
+        }
+        window_base = row[DIGITS - 1] + window_base;
+        table.push(row);
+    }
+
+    scalars
+        .iter()
+        .map(|s| {
+            let bytes = s.to_le_bytes();
+            let mut res = G1Projective::IDENTITY;
+            for (i, row) in table.iter().enumerate() {
+                let digit = (bytes[i / 2] >> ((i % 2) * FIXED_BASE_WINDOW)) & 0x0F;
+                let mut entry = G1Projective::IDENTITY;
+                for (j, point) in row.iter().enumerate() {
+                    entry.conditional_assign(point, (j as u8).ct_eq(&digit));
+                }
+                res += entry;
+            }
+            res
+        })
+        .collect()
+}
+
 impl core::ops::AddAssign for PolynomialG1 {
     fn add_assign(&mut self, rhs: Self) {
         let min_len = core::cmp::min(self.0.len(), rhs.0.len());
@@ -207,6 +299,10 @@ impl core::ops::MulAssign<&[Scalar; 2]> for Polynomial {
 
 impl core::ops::MulAssign<&[Scalar]> for Polynomial {
     fn mul_assign(&mut self, rhs: &[Scalar]) {
+        if self.0.len().min(rhs.len()) > NTT_THRESHOLD {
+            self.0 = ntt_mul(&self.0, rhs);
+            return;
+        }
         let orig = self.0.clone();
 
         // Both vectors can't be empty
@@ -235,3 +331,305 @@ impl core::ops::MulAssign<Scalar> for Polynomial {
         }
     }
 }
+
+/// Products where the smaller factor has at most this many coefficients
+/// are computed with schoolbook multiplication
+const NTT_THRESHOLD: usize = 32;
+
+/// Multiplies two non-empty polynomials with a number theoretic transform
+/// over the 2^32-th roots of unity of the scalar field
+fn ntt_mul(lhs: &[Scalar], rhs: &[Scalar]) -> Vec<Scalar> {
+    let len = lhs.len() + rhs.len() - 1;
+    let n = len.next_power_of_two();
+    let log_n = n.trailing_zeros();
+    assert!(
+        log_n <= Scalar::S,
+        "polynomial product is too large for the NTT"
+    );
+    let omega = (0..Scalar::S - log_n).fold(Scalar::ROOT_OF_UNITY, |w, _| w.square());
+
+    let mut a = lhs.to_vec();
+    a.resize(n, Scalar::ZERO);
+    let mut b = rhs.to_vec();
+    b.resize(n, Scalar::ZERO);
+    ntt(&mut a, omega);
+    ntt(&mut b, omega);
+    for (x, y) in a.iter_mut().zip(b.iter()) {
+        *x *= y;
+    }
+    // omega has order n, so it is invertible
+    ntt(&mut a, omega.invert().unwrap());
+    let n_inv = Scalar::from(n as u64).invert().unwrap();
+    a.truncate(len);
+    for x in a.iter_mut() {
+        *x *= n_inv;
+    }
+    a
+}
+
+/// In-place iterative radix-2 transform, `values.len()` must be the order of `omega`
+fn ntt(values: &mut [Scalar], omega: Scalar) {
+    let n = values.len();
+    let log_n = n.trailing_zeros();
+    if n <= 1 {
+        return;
+    }
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - log_n);
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        // w_len has order len
+        let w_len = (0..(n / len).trailing_zeros()).fold(omega, |w, _| w.square());
+        let half = len / 2;
+        let mut twiddles = Vec::with_capacity(half);
+        let mut w = Scalar::ONE;
+        for _ in 0..half {
+            twiddles.push(w);
+            w *= w_len;
+        }
+        for chunk in values.chunks_mut(len) {
+            let (lo, hi) = chunk.split_at_mut(half);
+            for ((u, v), w) in lo.iter_mut().zip(hi.iter_mut()).zip(twiddles.iter()) {
+                let t = *v * w;
+                *v = *u - t;
+                *u += t;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Inverts every value in place with a single field inversion
+/// (Montgomery's trick). If any value is zero, returns an error
+/// and leaves the values unchanged
+pub fn batch_invert(values: &mut [Scalar]) -> Result<(), &'static str> {
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        // Each chunk costs one inversion, which amortizes over this many products
+        const CHUNK: usize = 1024;
+        if values.par_iter().any(|v| bool::from(v.is_zero())) {
+            return Err("cannot invert zero");
+        }
+        values.par_chunks_mut(CHUNK).for_each(|chunk| {
+            // The chunk has no zeros, so this cannot fail
+            let _ = batch_invert_serial(chunk);
+        });
+        Ok(())
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        batch_invert_serial(values)
+    }
+}
+
+fn batch_invert_serial(values: &mut [Scalar]) -> Result<(), &'static str> {
+    // prefix[i] = values[0] * ... * values[i - 1]
+    let mut prefix = Vec::with_capacity(values.len());
+    let mut acc = Scalar::ONE;
+    for v in values.iter() {
+        prefix.push(acc);
+        acc *= v;
+    }
+    // The product is zero exactly when one of the values is
+    let mut inv = Option::<Scalar>::from(acc.invert()).ok_or("cannot invert zero")?;
+    for (v, p) in values.iter_mut().zip(prefix.iter()).rev() {
+        let t = inv * *p;
+        inv *= *v;
+        *v = t;
+    }
+    Ok(())
+}
+
+/// Runs both closures, on the rayon thread pool with the `parallel` feature
+pub(crate) fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        rayon::join(a, b)
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (a(), b())
+    }
+}
+
+/// Computes `sum_s weights[s] * prod_{j < s} (roots[j] - x)` with a
+/// subproduct tree in O(n log^2 n)
+pub(crate) fn weighted_prefix_products(roots: &[Scalar], weights: &[Scalar]) -> Polynomial {
+    debug_assert_eq!(roots.len(), weights.len());
+    if roots.is_empty() {
+        return Polynomial::default();
+    }
+    Polynomial(prefix_tree(roots, weights).1)
+}
+
+/// Computes `sum_s weights[s] * prod_{j > s} (roots[j] - x)` with a
+/// subproduct tree in O(n log^2 n)
+pub(crate) fn weighted_suffix_products(roots: &[Scalar], weights: &[Scalar]) -> Polynomial {
+    debug_assert_eq!(roots.len(), weights.len());
+    if roots.is_empty() {
+        return Polynomial::default();
+    }
+    Polynomial(suffix_tree(roots, weights).1)
+}
+
+/// Returns `(prod_j (roots[j] - x), sum_s weights[s] * prod_{j < s} (roots[j] - x))`
+fn prefix_tree(roots: &[Scalar], weights: &[Scalar]) -> (Vec<Scalar>, Vec<Scalar>) {
+    if roots.len() == 1 {
+        return (vec![roots[0], -Scalar::ONE], vec![weights[0]]);
+    }
+    let mid = roots.len() / 2;
+    let ((p_l, s_l), (p_r, s_r)) = join(
+        || prefix_tree(&roots[..mid], &weights[..mid]),
+        || prefix_tree(&roots[mid..], &weights[mid..]),
+    );
+    // The right half's terms also contain every root of the left half
+    let mut s = Polynomial(s_r);
+    s *= p_l.as_slice();
+    s += s_l.as_slice();
+    let mut p = Polynomial(p_l);
+    p *= p_r.as_slice();
+    (p.0, s.0)
+}
+
+/// Returns `(prod_j (roots[j] - x), sum_s weights[s] * prod_{j > s} (roots[j] - x))`
+fn suffix_tree(roots: &[Scalar], weights: &[Scalar]) -> (Vec<Scalar>, Vec<Scalar>) {
+    if roots.len() == 1 {
+        return (vec![roots[0], -Scalar::ONE], vec![weights[0]]);
+    }
+    let mid = roots.len() / 2;
+    let ((p_l, s_l), (p_r, s_r)) = join(
+        || suffix_tree(&roots[..mid], &weights[..mid]),
+        || suffix_tree(&roots[mid..], &weights[mid..]),
+    );
+    // The left half's terms also contain every root of the right half
+    let mut s = Polynomial(s_l);
+    s *= p_r.as_slice();
+    s += s_r.as_slice();
+    let mut p = Polynomial(p_l);
+    p *= p_r.as_slice();
+    (p.0, s.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_scalars(n: usize) -> Vec<Scalar> {
+        (0..n).map(|_| Scalar::random(rand::rngs::OsRng)).collect()
+    }
+
+    fn schoolbook(lhs: &[Scalar], rhs: &[Scalar]) -> Vec<Scalar> {
+        let mut res = vec![Scalar::ZERO; lhs.len() + rhs.len() - 1];
+        for (i, a) in lhs.iter().enumerate() {
+            for (j, b) in rhs.iter().enumerate() {
+                res[i + j] += a * b;
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn point_evaluation_test() {
+        let points: Vec<G1Projective> = (0..20)
+            .map(|_| G1Projective::GENERATOR * Scalar::random(rand::rngs::OsRng))
+            .collect();
+        let x = Scalar::random(rand::rngs::OsRng);
+        let mut expected = G1Projective::IDENTITY;
+        for p in points.iter().rev() {
+            expected = expected * x + p;
+        }
+        assert_eq!(PolynomialG1(points).evaluate(x), Some(expected));
+        assert_eq!(PolynomialG1(Vec::new()).evaluate(x), None);
+
+        let base = G1Projective::GENERATOR * x;
+        for n in [3, 40] {
+            let mut scalars = random_scalars(n);
+            scalars[0] = Scalar::ZERO;
+            scalars[1] = -Scalar::ONE;
+            let products = fixed_base_mul(base, &scalars);
+            assert!(products
+                .iter()
+                .zip(scalars.iter())
+                .all(|(p, s)| *p == base * s));
+        }
+    }
+
+    #[test]
+    fn ct_sum_of_products_test() {
+        for n in [0, 1, 2, 17] {
+            let points: Vec<G1Projective> = (0..n)
+                .map(|_| G1Projective::GENERATOR * Scalar::random(rand::rngs::OsRng))
+                .collect();
+            let mut scalars = random_scalars(n);
+            if n > 1 {
+                scalars[0] = Scalar::ZERO;
+                scalars[1] = -Scalar::ONE;
+            }
+            assert_eq!(
+                ct_sum_of_products(&points, &scalars),
+                G1Projective::sum_of_products(&points, &scalars)
+            );
+        }
+    }
+
+    #[test]
+    fn batch_invert_test() {
+        let mut values = random_scalars(2500);
+        let expected: Vec<Scalar> = values.iter().map(|v| v.invert().unwrap()).collect();
+        batch_invert(&mut values).unwrap();
+        assert_eq!(values, expected);
+
+        let mut values = random_scalars(5);
+        values[3] = Scalar::ZERO;
+        let orig = values.clone();
+        assert_eq!(batch_invert(&mut values), Err("cannot invert zero"));
+        assert_eq!(values, orig);
+        assert!(batch_invert(&mut []).is_ok());
+    }
+
+    #[test]
+    fn ntt_mul_test() {
+        for (l, r) in [(1, 1), (2, 3), (33, 33), (40, 100), (257, 64)] {
+            let lhs = random_scalars(l);
+            let rhs = random_scalars(r);
+            assert_eq!(ntt_mul(&lhs, &rhs), schoolbook(&lhs, &rhs));
+            let mut poly = Polynomial(lhs.clone());
+            poly *= rhs.as_slice();
+            assert_eq!(poly.0, schoolbook(&lhs, &rhs));
+        }
+    }
+
+    #[test]
+    fn weighted_products_test() {
+        let roots = random_scalars(70);
+        let weights = random_scalars(70);
+        let x = Scalar::random(rand::rngs::OsRng);
+
+        let mut prefix = Scalar::ZERO;
+        let mut suffix = Scalar::ZERO;
+        for s in 0..roots.len() {
+            prefix += roots[..s].iter().fold(weights[s], |acc, r| acc * (r - x));
+            suffix += roots[s + 1..]
+                .iter()
+                .fold(weights[s], |acc, r| acc * (r - x));
+        }
+        let p = weighted_prefix_products(&roots, &weights);
+        let q = weighted_suffix_products(&roots, &weights);
+        assert_eq!(p.0.len(), roots.len());
+        assert_eq!(q.0.len(), roots.len());
+        assert_eq!(p.eval(x), prefix);
+        assert_eq!(q.eval(x), suffix);
+        assert!(weighted_prefix_products(&[], &[]).0.is_empty());
+    }
+}
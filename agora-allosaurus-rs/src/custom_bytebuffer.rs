@@ -1,7 +1,7 @@
 use blsful::inner_types::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{MembershipProof, SECURITY_BYTES};
+use crate::{EpochMembershipProof, MembershipProof, NonMembershipProof, SECURITY_BYTES};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CustomStructForServerUpdate {
@@ -37,3 +37,15 @@ impl CustomStructForMembershipProof {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomStructForEpochMembershipProof {
+    pub proof: EpochMembershipProof,
+    pub challenge: [u8; 2*SECURITY_BYTES],
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomStructForNonMembershipProof {
+    pub proof: NonMembershipProof,
+    pub challenge: [u8; 2*SECURITY_BYTES],
+}
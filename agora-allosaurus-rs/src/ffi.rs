@@ -1,22 +1,32 @@
 #![allow(unused_doc_comments, missing_docs)]
+use crate::accumulator::Accumulator;
 use crate::accumulator::Coefficient;
 use crate::accumulator::Element;
+use crate::accumulator::SecretKey;
 use crate::utils::*;
 use crate::custom_bytebuffer::*;
 use ffi_support::{
     define_bytebuffer_destructor, define_handle_map_deleter, define_string_destructor, ByteBuffer,
-    ConcurrentHandleMap, ErrorCode, ExternError,HandleError, Handle
+    ConcurrentHandleMap, ErrorCode, ExternError, Handle
 };
 use blsful::inner_types::*;
 use lazy_static::lazy_static;
 use std::{ptr, slice, vec::Vec};
 use postcard;
+use serde::de::DeserializeOwned;
 use crate::accumulator::witness::MembershipWitness;
 
-use super::{servers::Server, witness::*, user::*};
+use super::{
+    presentation::*, rotation::{MigratedWitness, MigrationRequest}, servers::*, service::*,
+    verifier::*, wallet::WalletKey, witness::{ReissueRequest, Witness}, user::*,
+};
 
 lazy_static! {
     pub static ref SERVERS: ConcurrentHandleMap<Server> = ConcurrentHandleMap::new();
+    pub static ref WITNESS_SERVICES: ConcurrentHandleMap<WitnessUpdateService> =
+        ConcurrentHandleMap::new();
+    pub static ref VERIFIER_SESSIONS: ConcurrentHandleMap<VerifierSession> =
+        ConcurrentHandleMap::new();
 }
 
 /// Cleanup created strings
@@ -25,6 +35,10 @@ define_string_destructor!(allosaurus_string_free);
 define_bytebuffer_destructor!(allosaurus_byte_buffer_free);
 /// Cleanup created proof contexts
 define_handle_map_deleter!(SERVERS, allosaurus_create_proof_free);
+/// Cleanup created witness update services
+define_handle_map_deleter!(WITNESS_SERVICES, allosaurus_witness_service_free);
+/// Cleanup created verifier sessions
+define_handle_map_deleter!(VERIFIER_SESSIONS, allosaurus_verifier_session_free);
 
 /// Used for receiving byte arrays
 #[repr(C)]
@@ -111,35 +125,81 @@ pub fn to_fixed_array<const N: usize>(&self) -> Option<[u8; N]> {
     }
 }
 
+fn invalid_input(what: &str) -> ExternError {
+    ExternError::new_error(ErrorCode::new(-2), format!("invalid {}", what))
+}
+
+/// Deserializes caller-supplied bytes, reporting malformed input
+/// as an error instead of panicking across the FFI boundary
+fn decode<T: DeserializeOwned>(bytes: &ByteArray, what: &str) -> Result<T, ExternError> {
+    postcard::from_bytes(&bytes.to_vec()).map_err(|_| invalid_input(what))
+}
+
+fn decode_user(bytes: &ByteArray) -> Result<User, ExternError> {
+    User::from_bytes(&bytes.to_vec()).map_err(|_| invalid_input("user"))
+}
+
+fn decode_challenge(bytes: &ByteArray) -> Result<[u8; 2 * SECURITY_BYTES], ExternError> {
+    bytes.to_fixed_array().ok_or_else(|| invalid_input("challenge"))
+}
+
+fn decode_string(bytes: &ByteArray, what: &str) -> Result<String, ExternError> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| invalid_input(what))
+}
+
 #[no_mangle]
 pub extern "C" fn allosaurus_new_server(err: &mut ExternError) -> u64 {
     SERVERS.insert_with_output(err, || Server::new(&AccParams::default()))
 }
 
+#[no_mangle]
+pub extern "C" fn allosaurus_new_server_from_seed(seed: ByteArray, err: &mut ExternError) -> u64 {
+    SERVERS.insert_with_result(err, || {
+        <[u8; 32]>::try_from(seed.to_vec().as_slice())
+            .map(|seed| Server::from_seed(&AccParams::default(), seed))
+            .map_err(|_| ExternError::new_error(ErrorCode::new(-2), "seed must be 32 bytes".to_string()))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_new_server_from_keys(
+    witness_secret_key: ByteArray,
+    sign_secret_key: ByteArray,
+    revoked_secret_key: ByteArray,
+    accumulator: ByteArray,
+    err: &mut ExternError,
+) -> u64 {
+    SERVERS.insert_with_result(err, move || {
+        let alpha: SecretKey = decode(&witness_secret_key, "witness secret key")?;
+        let s_m: SecretKey = decode(&sign_secret_key, "signing secret key")?;
+        let revoked_secret_key: SecretKey = decode(&revoked_secret_key, "revoked secret key")?;
+        let accumulator: Accumulator = decode(&accumulator, "accumulator")?;
+        Ok::<Server, ExternError>(Server::from_keys(
+            &AccParams::default(),
+            alpha,
+            s_m,
+            revoked_secret_key,
+            accumulator,
+        ))
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn allosaurus_new_user(handle: u64, user: &mut ByteBuffer, err: &mut ExternError) -> i32 {
-    let handle = Handle::from_u64(handle).unwrap();
-    let result = SERVERS.get(handle, |server| {
+    let result = SERVERS.call_with_output(err, handle, |server| {
         let new_user = User::new(server, UserID::random());
-        let serialized_user = postcard::to_stdvec(&new_user).unwrap();
-        *user = ByteBuffer::from_vec(serialized_user);
-        Ok::<(), HandleError>(())
+        ByteBuffer::from_vec(new_user.to_bytes().unwrap())
     });
-    match result {
-        Ok(_) => 0,
-        Err(_) => {
-            *err = ExternError::new_error(ErrorCode::new(-2), "unable to create new user".to_string());
-            -1
-        }
-    };
+    if err.get_code().is_success() {
+        *user = result;
+    }
     err.get_code().code()
 }
 
 #[no_mangle]
 pub extern "C" fn allosaurus_server_add(handle: u64, user: ByteArray, witness_buffer: &mut ByteBuffer, err: &mut ExternError) -> i32 {
-    let deserial_user: User = postcard::from_bytes(&user.to_vec()).unwrap();
-    let user_id = deserial_user.get_id();
     let result = SERVERS.call_with_result_mut(err, handle, move |server| {
+        let user_id = decode_user(&user)?.get_id();
         server.add(user_id).map_or_else(
             || Err(ExternError::new_error(ErrorCode::new(-2), "unable to add user".to_string())),
             |witness| Ok(ByteBuffer::from_vec(postcard::to_stdvec(&witness).unwrap()))
@@ -153,9 +213,8 @@ pub extern "C" fn allosaurus_server_add(handle: u64, user: ByteArray, witness_bu
 
 #[no_mangle]
 pub extern "C" fn allosaurus_server_delete(handle: u64, user: ByteArray, acc_buffer: &mut ByteBuffer, err: &mut ExternError) -> i32 {
-    let deserial_user: User = postcard::from_bytes(&user.to_vec()).unwrap();
-    let user_id = deserial_user.get_id();
     let result = SERVERS.call_with_result_mut(err, handle, move |server| {
+        let user_id = decode_user(&user)?.get_id();
         server.delete(user_id).map_or_else(
             || Err(ExternError::new_error(ErrorCode::new(-2), "unable to delete user_id".to_string())),
             |acc| Ok(ByteBuffer::from_vec(postcard::to_stdvec(&acc).unwrap()))
@@ -176,19 +235,20 @@ pub extern "C" fn allosaurus_server_update(
     err: &mut ExternError,
 ) ->i32 {
     let user_bytes = unsafe { slice::from_raw_parts(user_bytes, user_cnt) };
-    let users: Vec<User> = user_bytes.iter().map(|user| postcard::from_bytes(&user.to_vec()).unwrap()).collect();
-    let user_ids: Vec<Scalar> = users.iter().map(|user| {
-        let user_id: UserID = user.get_id();
-        let Element(scalar) = user_id;
-        scalar
-    }).collect();
-    // println!("user_ids: {:?}", user_ids);
-    let result = SERVERS.call_with_output_mut(err, server_handle, move |server| {
-        let (ds, vs) = server.update(server.get_epoch(), &user_ids);
-        println!("ds: {:?}, vs: {:?}", ds, vs);
+    let result = SERVERS.call_with_result_mut(err, server_handle, move |server| {
+        let users = user_bytes.iter().map(decode_user).collect::<Result<Vec<User>, _>>()?;
+        let user_ids: Vec<Scalar> = users.iter().map(|user| {
+            let user_id: UserID = user.get_id();
+            let Element(scalar) = user_id;
+            scalar
+        }).collect();
+        // Covers every deletion the server still keeps
+        let (ds, vs) = server
+            .update(server.get_epoch() - server.first_epoch(), &user_ids)
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))?;
         let mut custom_struct = CustomStructForServerUpdate::new();
         custom_struct.add_multiple(ds, vs);
-        ByteBuffer::from_vec(postcard::to_stdvec(&custom_struct).unwrap())
+        Ok::<ByteBuffer, ExternError>(ByteBuffer::from_vec(postcard::to_stdvec(&custom_struct).unwrap()))
     });
     if err.get_code().is_success() {
         *result_buffer = result;
@@ -196,6 +256,36 @@ pub extern "C" fn allosaurus_server_update(
     err.get_code().code()
 }
 
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn allosaurus_server_batch_delete(
+    user_bytes: *const ByteArray,
+    user_cnt: usize,
+    server_handle: u64,
+    acc_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let user_bytes = unsafe { slice::from_raw_parts(user_bytes, user_cnt) };
+    let result = SERVERS.call_with_result_mut(err, server_handle, move |server| {
+        let users = user_bytes.iter().map(decode_user).collect::<Result<Vec<User>, _>>()?;
+        // Check every user first so a failed batch leaves the server unchanged
+        if users.iter().any(|user| !server.all_witnesses.contains_key(&user.get_id())) {
+            return Err(ExternError::new_error(ErrorCode::new(-2), "unable to delete user_id"));
+        }
+        let mut accumulator = server.get_accumulator();
+        for user in &users {
+            accumulator = server
+                .delete(user.get_id())
+                .ok_or_else(|| ExternError::new_error(ErrorCode::new(-2), "unable to delete user_id"))?;
+        }
+        Ok(ByteBuffer::from_vec(postcard::to_stdvec(&accumulator).unwrap()))
+    });
+    if err.get_code().is_success() {
+        *acc_buffer = result;
+    }
+    err.get_code().code()
+}
+
 #[no_mangle]
 pub extern "C" fn allosaurus_server_get_epoch(handle: u64, err: &mut ExternError) -> i32 {
     let result = SERVERS.call_with_output_mut(err, handle, |server| {
@@ -263,30 +353,161 @@ pub extern "C" fn allosaurus_user_create_witness(
     user_buffer: &mut ByteBuffer,
     err: &mut ExternError,
 ) -> i32 {
-    let mut user: User = postcard::from_bytes(&user.to_vec()).unwrap();
-    let result = SERVERS.get(Handle::from_u64(server_handle).unwrap(), |server| {
-        user.create_witness(&AccParams::default(), &server);
-        Ok::<User, HandleError>(user)
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let mut user: User = decode_user(&user)?;
+        user.create_witness(&AccParams::default(), server);
+        Ok::<ByteBuffer, ExternError>(ByteBuffer::from_vec(user.to_bytes().unwrap()))
     });
-    match result {
-        Ok(user) => {
-            *user_buffer = ByteBuffer::from_vec(postcard::to_stdvec(&user).unwrap());
-            0
-        },
-        Err(_) => {
-            *err = ExternError::new_error(ErrorCode::new(-2), "unable to create witness".to_string());
-            -1
-        }
-    };
+    if err.get_code().is_success() {
+        *user_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_make_reissue_request(
+    user: ByteArray,
+    request_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let user: User = decode_user(&user)?;
+        user.make_reissue_request(&AccParams::default())
+            .map(|request| ByteBuffer::from_vec(postcard::to_stdvec(&request).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *request_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_reissue_witness(
+    server_handle: u64,
+    request: ByteArray,
+    reissued_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let request: ReissueRequest = decode(&request, "reissue request")?;
+        server
+            .reissue(&AccParams::default(), &request)
+            .map(|reissued| ByteBuffer::from_vec(postcard::to_stdvec(&reissued).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *reissued_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_apply_reissued_witness(
+    user: ByteArray,
+    reissued: ByteArray,
+    user_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let mut user: User = decode_user(&user)?;
+        let reissued: ReissuedWitness = decode(&reissued, "reissued witness")?;
+        user.apply_reissued_witness(&AccParams::default(), &reissued)
+            .map(|_| ByteBuffer::from_vec(user.to_bytes().unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *user_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_server_rotate_keys(
+    handle: u64,
+    grace_until: u64,
+    linkage_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result_mut(err, handle, move |server| {
+        server
+            .rotate_keys(&AccParams::default(), grace_until)
+            .map(|linkage| ByteBuffer::from_vec(postcard::to_stdvec(&linkage).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *linkage_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_make_migration_request(
+    user: ByteArray,
+    request_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let user: User = decode_user(&user)?;
+        user.make_migration_request(&AccParams::default())
+            .map(|request| ByteBuffer::from_vec(postcard::to_stdvec(&request).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *request_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_migrate_witness(
+    server_handle: u64,
+    request: ByteArray,
+    now: u64,
+    migrated_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let request: MigrationRequest = decode(&request, "migration request")?;
+        server
+            .migrate(&AccParams::default(), &request, now)
+            .map(|migrated| ByteBuffer::from_vec(postcard::to_stdvec(&migrated).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *migrated_buffer = result;
+    }
     err.get_code().code()
 }
 
+#[no_mangle]
+pub extern "C" fn allosaurus_user_apply_migrated_witness(
+    user: ByteArray,
+    migrated: ByteArray,
+    user_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let mut user: User = decode_user(&user)?;
+        let migrated: MigratedWitness = decode(&migrated, "migrated witness")?;
+        user.apply_migrated_witness(&AccParams::default(), &migrated)
+            .map(|_| ByteBuffer::from_vec(user.to_bytes().unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *user_buffer = result;
+    }
+    err.get_code().code()
+}
 
 #[no_mangle]
 pub extern "C" fn allosaurus_user_check_witness(
     user: ByteArray,
 ) -> i32 {
-    let user: User = postcard::from_bytes(&user.to_vec()).unwrap();
+    let user: User = match decode_user(&user) {
+        Ok(user) => user,
+        Err(_) => return -2,
+    };
     let params = AccParams::default();
     match user.check_witness(&params, &user.get_accumulator()) {
         Ok(_) => 0,
@@ -302,52 +523,75 @@ pub extern "C" fn allosaurus_user_make_membership_proof(
     proof_buffer: &mut ByteBuffer,
     err: &mut ExternError
 ) -> i32 {
-    let user: User = postcard::from_bytes(&user.to_vec()).unwrap();
     let params = AccParams::default();
-    let challenge = challenge.to_fixed_array().unwrap();
-    let result = SERVERS.get(Handle::from_u64(server_handle).unwrap(), |server| {
-        let proof = user.make_membership_proof(&params, &server.get_public_keys(), &challenge);
-        let custom_membership_proof = CustomStructForMembershipProof::new(proof.unwrap(), challenge);
-        let buffer = ByteBuffer::from_vec(postcard::to_stdvec(&custom_membership_proof).unwrap());
-        Ok::<ByteBuffer, HandleError>(buffer)
-    });
-    match result {
-        Ok(proof) => {
-            *proof_buffer = proof;
-            0
-        },
-        Err(_) => {
-            *err = ExternError::new_error(ErrorCode::new(-2), "Make membership proof failed".to_string());
-            -1
-        }
-    };
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let user: User = decode_user(&user)?;
+        let challenge = decode_challenge(&challenge)?;
+        user.make_membership_proof(&params, &server.get_public_keys(), &challenge)
+            .map(|proof| {
+                let proof = CustomStructForMembershipProof::new(proof, challenge);
+                ByteBuffer::from_vec(postcard::to_stdvec(&proof).unwrap())
+            })
+            .ok_or_else(|| ExternError::new_error(ErrorCode::new(-2), "Make membership proof failed"))
+    });
+    if err.get_code().is_success() {
+        *proof_buffer = result;
+    }
     err.get_code().code()
 }
 
+/// Checks a membership proof against the challenge it carries, which the
+/// prover chose, so the proof can be replayed. Kept for existing callers;
+/// verifiers should use `allosaurus_verifier_check_membership_proof`
 #[no_mangle]
 pub extern "C" fn allosaurus_witness_check_membership_proof(
     server_handle: u64,
     proof: ByteArray,
     err: &mut ExternError
 ) -> i32 {
-    let custom_membership_proof: CustomStructForMembershipProof = postcard::from_bytes(&proof.to_vec()).unwrap();
     let params = AccParams::default();
-    let result = SERVERS.get(Handle::from_u64(server_handle).unwrap(), |server| {
-        let proof = custom_membership_proof.proof;
-        let challenge = custom_membership_proof.challenge;
-        if Witness::check_membership_proof(&proof, &params, &server.get_public_keys(), &server.get_accumulator(), &challenge) {
-            Ok::<(), HandleError>(())
+    SERVERS.call_with_result(err, server_handle, move |server| {
+        let proof: CustomStructForMembershipProof = decode(&proof, "proof")?;
+        if Witness::check_membership_proof(
+            &proof.proof,
+            &params,
+            &server.get_public_keys(),
+            &server.get_accumulator(),
+            &proof.challenge,
+        ) {
+            Ok(())
         } else {
-            Err(HandleError::NullHandle)
+            Err(ExternError::new_error(ErrorCode::new(-2), "Verify membership proof failed"))
         }
     });
-    match result {
-        Ok(_) => 0,
-        Err(_) => {
-            *err = ExternError::new_error(ErrorCode::new(-2), "Verify membership proof failed".to_string());
-            -1
-        }
-    };
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_verifier_check_membership_proof(
+    session_handle: u64,
+    server_handle: u64,
+    proof: ByteArray,
+    now: u64,
+    err: &mut ExternError
+) -> i32 {
+    let params = AccParams::default();
+    VERIFIER_SESSIONS.call_with_result_mut(err, session_handle, move |session| {
+        let proof: CustomStructForMembershipProof = decode(&proof, "proof")?;
+        let server_handle = Handle::from_u64(server_handle)?;
+        SERVERS.get(server_handle, |server| {
+            session
+                .verify_membership_proof(
+                    &proof.proof,
+                    &proof.challenge,
+                    &params,
+                    &server.get_public_keys(),
+                    &server.get_accumulator(),
+                    now,
+                )
+                .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+        })
+    });
     err.get_code().code()
 }
 
@@ -361,20 +605,33 @@ pub extern "C" fn allosaurus_user_update(
     err: &mut ExternError,
 ) -> i32{ 
     let mut tmp_err = ExternError::default();
-    let mut user: User = postcard::from_bytes(&user.to_vec()).unwrap();
+    let mut user: User = match decode_user(&user) {
+        Ok(user) => user,
+        Err(e) => {
+            *err = e;
+            return -1;
+        }
+    };
     let server_handles = unsafe { slice::from_raw_parts(server_list, server_cnt) };
+    if server_handles.is_empty() {
+        *err = invalid_input("server list");
+        return -1;
+    }
     let mut server_refs = Vec::<Server>::with_capacity(server_cnt);
-    server_handles.iter().for_each(|&handle| {
+    for &handle in server_handles {
         let result = SERVERS.call_with_output(&mut tmp_err, handle, |server| {    
             ByteBuffer::from_vec(postcard::to_stdvec(server).unwrap())
         });
-        let server = postcard::from_bytes(result.as_slice()).unwrap();
+        if !tmp_err.get_code().is_success() {
+            *err = tmp_err;
+            return -1;
+        }
+        let server = postcard::from_bytes(result.destroy_into_vec().as_slice()).unwrap();
         server_refs.push(server);
-
-    });
+    }
     match user.update(&server_refs[..], threshold as usize) {
         Ok(()) => {
-            *new_user = ByteBuffer::from_vec(postcard::to_stdvec(&user).unwrap());
+            *new_user = ByteBuffer::from_vec(user.to_bytes().unwrap());
             0
         },
         Err(_) => {
@@ -385,6 +642,45 @@ pub extern "C" fn allosaurus_user_update(
     }
 }
 
+#[no_mangle]
+pub extern "C" fn allosaurus_user_export(
+    user: ByteArray,
+    password: ByteArray,
+    wallet_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let user: User = decode_user(&user)?;
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        user.export_encrypted(WalletKey::Password(&password))
+            .map(ByteBuffer::from_vec)
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *wallet_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_import(
+    wallet: ByteArray,
+    password: ByteArray,
+    user_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        User::import_encrypted(&wallet.to_vec(), WalletKey::Password(&password))
+            .map(|user| ByteBuffer::from_vec(user.to_bytes().unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *user_buffer = result;
+    }
+    err.get_code().code()
+}
+
 #[no_mangle]
 pub extern "C" fn witness_multi_batch_update(
     current_witness: ByteArray,
@@ -396,21 +692,31 @@ pub extern "C" fn witness_multi_batch_update(
     witness_buffer: &mut ByteBuffer,
 ) -> i32 {
     // Deserialize the MembershipWitness 
-    let mut current_witness: MembershipWitness = postcard::from_bytes(&current_witness.to_vec()).unwrap();
+    let mut current_witness: MembershipWitness = match postcard::from_bytes(&current_witness.to_vec()) {
+        Ok(witness) => witness,
+        Err(_) => return -2,
+    };
 
     // Deserialize y Element
-    let y_element: Element = postcard::from_bytes(&y_element.to_vec()).unwrap();
+    let y_element: Element = match postcard::from_bytes(&y_element.to_vec()) {
+        Ok(y) => y,
+        Err(_) => return -2,
+    };
     
     // Deserialize d_list and c_list in loop and store them in a vector
-    let d_elements: Vec<Element> = unsafe { slice::from_raw_parts(d_list, d_cnt) }
+    let d_elements: Option<Vec<Element>> = unsafe { slice::from_raw_parts(d_list, d_cnt) }
         .iter()
-        .map(|d| Element::from_bytes(d.to_fixed_array().unwrap()).unwrap())
+        .map(|d| Element::from_bytes(d.to_fixed_array()?))
         .collect();
 
-    let c_coefficients: Vec<Coefficient> = unsafe { slice::from_raw_parts(c_list, c_cnt) }
+    let c_coefficients: Option<Vec<Coefficient>> = unsafe { slice::from_raw_parts(c_list, c_cnt) }
         .iter()
-        .map(|c| Coefficient::from_bytes(c.to_fixed_array().unwrap()).unwrap())
+        .map(|c| Coefficient::from_bytes(c.to_fixed_array()?))
         .collect();
+    let (d_elements, c_coefficients) = match (d_elements, c_coefficients) {
+        (Some(d), Some(c)) => (d, c),
+        _ => return -2,
+    };
 
     let empty_a: Vec<Element> = Vec::new();
     let deltas: Vec<(_, _, _)> = std::iter::repeat(empty_a.as_slice())  
@@ -426,6 +732,416 @@ pub extern "C" fn witness_multi_batch_update(
 
 
 
+#[no_mangle]
+pub extern "C" fn allosaurus_server_publish_split_updates(
+    handle: u64,
+    epoch: u64,
+    slice_size: u64,
+    updates_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result(err, handle, |server| {
+        server
+            .publish_split_updates_since(epoch as usize, slice_size as usize)
+            .map(|updates| ByteBuffer::from_vec(postcard::to_stdvec(&updates).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *updates_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_new_witness_service(
+    publication: ByteArray,
+    retain_epochs: u64,
+    err: &mut ExternError,
+) -> u64 {
+    WITNESS_SERVICES.insert_with_result(err, move || {
+        let publication: AccumulatorPublication = decode(&publication, "publication")?;
+        WitnessUpdateService::new(
+            publication.epoch,
+            publication.accumulator,
+            publication.public_keys,
+            retain_epochs as usize,
+        )
+        .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_witness_service_get_epoch(
+    handle: u64,
+    epoch: &mut u64,
+    err: &mut ExternError,
+) -> i32 {
+    let result = WITNESS_SERVICES.call_with_output(err, handle, |service| {
+        service.get_epoch() as u64
+    });
+    if err.get_code().is_success() {
+        *epoch = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_witness_service_register(
+    handle: u64,
+    user_id: ByteArray,
+    witness: ByteArray,
+    epoch: u64,
+    err: &mut ExternError,
+) -> i32 {
+    WITNESS_SERVICES.call_with_result_mut(err, handle, move |service| {
+        let user_id: UserID = decode(&user_id, "user ID")?;
+        let witness: MembershipWitness = decode(&witness, "witness")?;
+        service
+            .register(user_id, witness, epoch as usize)
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_witness_service_ingest(
+    handle: u64,
+    updates: ByteArray,
+    revoked_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = WITNESS_SERVICES.call_with_result_mut(err, handle, move |service| {
+        let updates: Vec<SplitUpdate> = decode(&updates, "updates")?;
+        let mut revoked = Vec::new();
+        for update in updates {
+            revoked.extend(
+                service
+                    .ingest(update)
+                    .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))?,
+            );
+        }
+        Ok::<ByteBuffer, ExternError>(ByteBuffer::from_vec(postcard::to_stdvec(&revoked).unwrap()))
+    });
+    if err.get_code().is_success() {
+        *revoked_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_witness_service_get_witness(
+    handle: u64,
+    user_id: ByteArray,
+    witness_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = WITNESS_SERVICES.call_with_result_mut(err, handle, move |service| {
+        let user_id: UserID = decode(&user_id, "user ID")?;
+        service
+            .get_witness(&user_id)
+            .map(|tracked| ByteBuffer::from_vec(postcard::to_stdvec(&tracked.witness).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *witness_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_make_epoch_membership_proof(
+    server_handle: u64,
+    user: ByteArray,
+    challenge: ByteArray,
+    proof_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let params = AccParams::default();
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let user: User = decode_user(&user)?;
+        let challenge = decode_challenge(&challenge)?;
+        user.make_epoch_membership_proof(&params, &server.get_public_keys(), &challenge)
+            .map(|proof| {
+                let proof = CustomStructForEpochMembershipProof { proof, challenge };
+                ByteBuffer::from_vec(postcard::to_stdvec(&proof).unwrap())
+            })
+            .ok_or_else(|| ExternError::new_error(ErrorCode::new(-2), "Make membership proof failed"))
+    });
+    if err.get_code().is_success() {
+        *proof_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_server_publish_accumulator_history(
+    handle: u64,
+    window: u64,
+    history_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_output(err, handle, |server| {
+        let history = server.publish_accumulator_history(window as usize);
+        ByteBuffer::from_vec(postcard::to_stdvec(&history).unwrap())
+    });
+    if err.get_code().is_success() {
+        *history_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_verifier_check_epoch_membership_proof(
+    session_handle: u64,
+    public_keys: ByteArray,
+    history: ByteArray,
+    proof: ByteArray,
+    window: u64,
+    now: u64,
+    err: &mut ExternError,
+) -> i32 {
+    let params = AccParams::default();
+    VERIFIER_SESSIONS.call_with_result_mut(err, session_handle, move |session| {
+        let public_keys: PublicKeys = decode(&public_keys, "public keys")?;
+        let history: Vec<(usize, Accumulator)> = decode(&history, "accumulator history")?;
+        let proof: CustomStructForEpochMembershipProof = decode(&proof, "proof")?;
+        session
+            .verify_epoch_membership_proof(
+                &proof.proof,
+                &proof.challenge,
+                &params,
+                &public_keys,
+                &history,
+                window as usize,
+                now,
+            )
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_new_verifier_session(
+    verifier_id: ByteArray,
+    ttl: u64,
+    capacity: u64,
+    err: &mut ExternError,
+) -> u64 {
+    VERIFIER_SESSIONS.insert_with_result(err, move || {
+        let verifier_id = verifier_id
+            .to_fixed_array()
+            .ok_or_else(|| invalid_input("verifier ID"))?;
+        Ok::<VerifierSession, ExternError>(VerifierSession::new(verifier_id, ttl, capacity as usize))
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_verifier_issue_challenge(
+    session_handle: u64,
+    now: u64,
+    challenge_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = VERIFIER_SESSIONS.call_with_result_mut(err, session_handle, |session| {
+        session
+            .issue_challenge(now)
+            .map(|challenge| ByteBuffer::from_vec(challenge.to_vec()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *challenge_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_create_non_membership_witness(
+    server_handle: u64,
+    user: ByteArray,
+    user_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let mut user: User = decode_user(&user)?;
+        user.create_non_membership_witness(&AccParams::default(), server)
+            .map(|_| ByteBuffer::from_vec(user.to_bytes().unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *user_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_server_publish_revoked_set_updates(
+    handle: u64,
+    epoch: u64,
+    updates_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result(err, handle, |server| {
+        server
+            .publish_revoked_set_updates_since(epoch as usize)
+            .map(|updates| ByteBuffer::from_vec(postcard::to_stdvec(&updates).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *updates_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_server_prune(
+    handle: u64,
+    retain_epochs: u64,
+    checkpoint_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result_mut(err, handle, move |server| {
+        server
+            .prune(retain_epochs as usize)
+            .map(|checkpoint| ByteBuffer::from_vec(postcard::to_stdvec(&checkpoint).unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *checkpoint_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_apply_revoked_set_updates(
+    user: ByteArray,
+    updates: ByteArray,
+    user_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let mut user: User = decode_user(&user)?;
+        let updates: Vec<RevokedSetUpdate> = decode(&updates, "updates")?;
+        user.apply_revoked_set_updates(&updates)
+            .map(|_| ByteBuffer::from_vec(user.to_bytes().unwrap()))
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    if err.get_code().is_success() {
+        *user_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_make_non_membership_proof(
+    user: ByteArray,
+    challenge: ByteArray,
+    proof_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = ffi_support::call_with_result(err, move || {
+        let user: User = decode_user(&user)?;
+        let challenge = decode_challenge(&challenge)?;
+        user.make_non_membership_proof(&challenge)
+            .map(|proof| {
+                let proof = CustomStructForNonMembershipProof { proof, challenge };
+                ByteBuffer::from_vec(postcard::to_stdvec(&proof).unwrap())
+            })
+            .ok_or_else(|| ExternError::new_error(ErrorCode::new(-2), "Make non-membership proof failed"))
+    });
+    if err.get_code().is_success() {
+        *proof_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_server_check_non_membership_proof(
+    session_handle: u64,
+    server_handle: u64,
+    proof: ByteArray,
+    now: u64,
+    err: &mut ExternError,
+) -> i32 {
+    VERIFIER_SESSIONS.call_with_result_mut(err, session_handle, move |session| {
+        let proof: CustomStructForNonMembershipProof = decode(&proof, "proof")?;
+        let server_handle = Handle::from_u64(server_handle)?;
+        SERVERS.get(server_handle, |server| {
+            session
+                .verify_non_membership_proof(
+                    &proof.proof,
+                    &proof.challenge,
+                    &server.get_revoked_accumulator(),
+                    &server.get_revoked_public_key(),
+                    now,
+                )
+                .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+        })
+    });
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_server_publish(
+    handle: u64,
+    registry_id: ByteArray,
+    publication_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let result = SERVERS.call_with_result(err, handle, move |server| {
+        let registry_id = decode_string(&registry_id, "registry ID")?;
+        Ok::<ByteBuffer, ExternError>(ByteBuffer::from_vec(
+            postcard::to_stdvec(&server.publish(&registry_id)).unwrap(),
+        ))
+    });
+    if err.get_code().is_success() {
+        *publication_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_user_make_non_revocation_presentation(
+    server_handle: u64,
+    user: ByteArray,
+    registry_id: ByteArray,
+    challenge: ByteArray,
+    presentation_buffer: &mut ByteBuffer,
+    err: &mut ExternError,
+) -> i32 {
+    let params = AccParams::default();
+    let result = SERVERS.call_with_result(err, server_handle, move |server| {
+        let user: User = decode_user(&user)?;
+        let registry_id = decode_string(&registry_id, "registry ID")?;
+        let challenge = decode_challenge(&challenge)?;
+        user.make_non_revocation_presentation(&registry_id, &params, &server.get_public_keys(), &challenge)
+            .map(|presentation| ByteBuffer::from_vec(postcard::to_stdvec(&presentation).unwrap()))
+            .ok_or_else(|| ExternError::new_error(ErrorCode::new(-2), "Make presentation failed"))
+    });
+    if err.get_code().is_success() {
+        *presentation_buffer = result;
+    }
+    err.get_code().code()
+}
+
+#[no_mangle]
+pub extern "C" fn allosaurus_check_non_revocation_presentation(
+    session_handle: u64,
+    presentation: ByteArray,
+    publication: ByteArray,
+    now: u64,
+    err: &mut ExternError,
+) -> i32 {
+    let params = AccParams::default();
+    VERIFIER_SESSIONS.call_with_result_mut(err, session_handle, move |session| {
+        let presentation: NonRevocationPresentation = decode(&presentation, "presentation")?;
+        let publication: AccumulatorPublication = decode(&publication, "publication")?;
+        session
+            .verify_presentation(&presentation, &publication, &params, now)
+            .map_err(|e| ExternError::new_error(ErrorCode::new(-2), e.to_string()))
+    });
+    err.get_code().code()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -441,4 +1157,272 @@ mod tests {
         let result = User::check_witness(&user, &params, &server.get_accumulator());
         assert_eq!(result, Ok(()));
     }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        let garbage = vec![0xffu8; 3];
+        let mut err = ExternError::default();
+        let mut buffer = ByteBuffer::default();
+        let code = allosaurus_user_export(
+            ByteArray::from(&garbage),
+            ByteArray::from(&garbage),
+            &mut buffer,
+            &mut err,
+        );
+        assert_ne!(code, 0);
+        assert!(!err.get_code().is_success());
+
+        let mut err = ExternError::default();
+        let verifier_id = vec![7u8; 32];
+        let session = allosaurus_new_verifier_session(ByteArray::from(&verifier_id), 60, 16, &mut err);
+        let code = allosaurus_check_non_revocation_presentation(
+            session,
+            ByteArray::from(&garbage),
+            ByteArray::from(&garbage),
+            0,
+            &mut err,
+        );
+        assert_ne!(code, 0);
+
+        let mut err = ExternError::default();
+        let server = allosaurus_new_server(&mut err);
+        let mut buffer = ByteBuffer::default();
+        assert_ne!(allosaurus_server_add(server, ByteArray::from(&garbage), &mut buffer, &mut err), 0);
+        let mut err = ExternError::default();
+        let code = allosaurus_user_make_membership_proof(
+            server,
+            ByteArray::from(&garbage),
+            ByteArray::from(&garbage),
+            &mut buffer,
+            &mut err,
+        );
+        assert_ne!(code, 0);
+        assert_ne!(allosaurus_user_check_witness(ByteArray::from(&garbage)), 0);
+        let mut err = ExternError::default();
+        let code = allosaurus_user_update(&server, 1, ByteArray::from(&garbage), 2, &mut buffer, &mut err);
+        assert_ne!(code, 0);
+    }
+
+    #[test]
+    fn batch_delete_and_server_update() {
+        let params = AccParams::default();
+        let mut err = ExternError::default();
+        let server = allosaurus_new_server(&mut err);
+        let users = SERVERS.call_with_output_mut(&mut err, server, |server| {
+            let users: Vec<Vec<u8>> = (0..3)
+                .map(|_| {
+                    let mut user = User::new(server, UserID::random());
+                    server.add(user.get_id());
+                    user.create_witness(&params, server);
+                    user.to_bytes().unwrap()
+                })
+                .collect();
+            ByteBuffer::from_vec(postcard::to_stdvec(&users).unwrap())
+        });
+        let users: Vec<Vec<u8>> = postcard::from_bytes(&users.destroy_into_vec()).unwrap();
+        let deleted: Vec<ByteArray> = users[..2].iter().map(ByteArray::from).collect();
+
+        let mut accumulator = ByteBuffer::default();
+        assert_eq!(allosaurus_server_batch_delete(deleted.as_ptr(), 2, server, &mut accumulator, &mut err), 0);
+        let accumulator: Accumulator = postcard::from_bytes(&accumulator.destroy_into_vec()).unwrap();
+        let epoch = SERVERS.call_with_output(&mut err, server, |server| {
+            assert_eq!(server.get_accumulator(), accumulator);
+            server.get_epoch() as u64
+        });
+        assert_eq!(epoch, 3);
+        // Deleting an unknown user again leaves the server unchanged
+        let mut buffer = ByteBuffer::default();
+        assert_ne!(allosaurus_server_batch_delete(deleted.as_ptr(), 2, server, &mut buffer, &mut err), 0);
+
+        let mut err = ExternError::default();
+        let shares = [ByteArray::from(&users[2])];
+        let mut update = ByteBuffer::default();
+        assert_eq!(allosaurus_server_update(shares.as_ptr(), 1, server, &mut update, &mut err), 0);
+        let update: CustomStructForServerUpdate = postcard::from_bytes(&update.destroy_into_vec()).unwrap();
+        assert_eq!(update.ds.len(), 2);
+    }
+
+    #[test]
+    fn server_from_keys() {
+        let server = Server::new(&AccParams::default());
+        let alpha = postcard::to_stdvec(&server.witness_secret_key).unwrap();
+        let s_m = postcard::to_stdvec(&server.sign_secret_key).unwrap();
+        let revoked = postcard::to_stdvec(&server.revoked_secret_key).unwrap();
+        let accumulator = postcard::to_stdvec(&server.get_accumulator()).unwrap();
+        let mut err = ExternError::default();
+        let handle = allosaurus_new_server_from_keys(
+            ByteArray::from(&alpha),
+            ByteArray::from(&s_m),
+            ByteArray::from(&revoked),
+            ByteArray::from(&accumulator),
+            &mut err,
+        );
+        assert!(err.get_code().is_success());
+        let matches = SERVERS.call_with_output(&mut err, handle, |rebuilt| {
+            rebuilt.get_public_keys().accumulator_id() == server.get_public_keys().accumulator_id()
+                && rebuilt.get_revoked_public_key() == server.get_revoked_public_key()
+        });
+        assert_eq!(matches, 1);
+    }
+
+    #[test]
+    fn reissue_sends_only_the_request() {
+        let params = AccParams::default();
+        let mut err = ExternError::default();
+        let server = allosaurus_new_server(&mut err);
+        let user = SERVERS.call_with_output_mut(&mut err, server, |server| {
+            let mut user = User::new(server, UserID::random());
+            server.add(user.get_id());
+            user.create_witness(&params, server);
+            ByteBuffer::from_vec(user.to_bytes().unwrap())
+        });
+        let user = user.destroy_into_vec();
+
+        let mut request = ByteBuffer::default();
+        assert_eq!(allosaurus_user_make_reissue_request(ByteArray::from(&user), &mut request, &mut err), 0);
+        let request = request.destroy_into_vec();
+        let mut reissued = ByteBuffer::default();
+        assert_eq!(allosaurus_user_reissue_witness(server, ByteArray::from(&request), &mut reissued, &mut err), 0);
+        let reissued = reissued.destroy_into_vec();
+        let mut updated = ByteBuffer::default();
+        let code = allosaurus_user_apply_reissued_witness(
+            ByteArray::from(&user),
+            ByteArray::from(&reissued),
+            &mut updated,
+            &mut err,
+        );
+        assert_eq!(code, 0);
+        let updated = User::from_bytes(&updated.destroy_into_vec()).unwrap();
+        assert!(updated.check_witness(&params, &updated.get_accumulator()).is_ok());
+    }
+
+    #[test]
+    fn migration_sends_only_the_request() {
+        let params = AccParams::default();
+        let mut err = ExternError::default();
+        let server = allosaurus_new_server(&mut err);
+        let user = SERVERS.call_with_output_mut(&mut err, server, |server| {
+            let mut user = User::new(server, UserID::random());
+            server.add(user.get_id());
+            user.create_witness(&params, server);
+            ByteBuffer::from_vec(user.to_bytes().unwrap())
+        });
+        let user = user.destroy_into_vec();
+        let mut linkage = ByteBuffer::default();
+        assert_eq!(allosaurus_server_rotate_keys(server, 200, &mut linkage, &mut err), 0);
+        linkage.destroy_into_vec();
+
+        let mut request = ByteBuffer::default();
+        assert_eq!(allosaurus_user_make_migration_request(ByteArray::from(&user), &mut request, &mut err), 0);
+        let request = request.destroy_into_vec();
+        let mut migrated = ByteBuffer::default();
+        assert_eq!(allosaurus_user_migrate_witness(server, ByteArray::from(&request), 100, &mut migrated, &mut err), 0);
+        let migrated = migrated.destroy_into_vec();
+        let mut updated = ByteBuffer::default();
+        let code = allosaurus_user_apply_migrated_witness(
+            ByteArray::from(&user),
+            ByteArray::from(&migrated),
+            &mut updated,
+            &mut err,
+        );
+        assert_eq!(code, 0);
+        let updated = User::from_bytes(&updated.destroy_into_vec()).unwrap();
+        assert!(updated.check_witness(&params, &updated.get_accumulator()).is_ok());
+    }
+
+    #[test]
+    fn membership_proofs_redeem_verifier_challenges() {
+        let params = AccParams::default();
+        let mut err = ExternError::default();
+        let server = allosaurus_new_server(&mut err);
+        let user = SERVERS.call_with_output_mut(&mut err, server, |server| {
+            let mut user = User::new(server, UserID::random());
+            server.add(user.get_id());
+            user.create_witness(&params, server);
+            ByteBuffer::from_vec(user.to_bytes().unwrap())
+        });
+        let user = user.destroy_into_vec();
+        let verifier_id = vec![7u8; 32];
+        let session = allosaurus_new_verifier_session(ByteArray::from(&verifier_id), 60, 16, &mut err);
+        assert!(err.get_code().is_success());
+
+        let mut challenge = ByteBuffer::default();
+        assert_eq!(allosaurus_verifier_issue_challenge(session, 1000, &mut challenge, &mut err), 0);
+        let challenge = challenge.destroy_into_vec();
+        let mut proof = ByteBuffer::default();
+        let code = allosaurus_user_make_membership_proof(
+            server,
+            ByteArray::from(&user),
+            ByteArray::from(&challenge),
+            &mut proof,
+            &mut err,
+        );
+        assert_eq!(code, 0);
+        let proof = proof.destroy_into_vec();
+        let code =
+            allosaurus_verifier_check_membership_proof(session, server, ByteArray::from(&proof), 1001, &mut err);
+        assert_eq!(code, 0);
+
+        // The challenge was redeemed by the first check
+        let mut err = ExternError::default();
+        let code =
+            allosaurus_verifier_check_membership_proof(session, server, ByteArray::from(&proof), 1002, &mut err);
+        assert_ne!(code, 0);
+    }
+
+    #[test]
+    fn non_membership_proofs_redeem_verifier_challenges() {
+        let params = AccParams::default();
+        let mut err = ExternError::default();
+        let server = allosaurus_new_server(&mut err);
+        let user = SERVERS.call_with_output_mut(&mut err, server, |server| {
+            let mut user = User::new(server, UserID::random());
+            server.add(user.get_id());
+            user.create_witness(&params, server);
+            user.create_non_membership_witness(&params, server).unwrap();
+            ByteBuffer::from_vec(user.to_bytes().unwrap())
+        });
+        let user = user.destroy_into_vec();
+        let verifier_id = vec![8u8; 32];
+        let session = allosaurus_new_verifier_session(ByteArray::from(&verifier_id), 60, 16, &mut err);
+        assert!(err.get_code().is_success());
+
+        // A proof for a challenge the verifier did not issue is rejected
+        let mut proof = ByteBuffer::default();
+        let code = allosaurus_user_make_non_membership_proof(
+            ByteArray::from(&user),
+            ByteArray::from(&[3u8; 32].to_vec()),
+            &mut proof,
+            &mut err,
+        );
+        assert_eq!(code, 0);
+        let proof = proof.destroy_into_vec();
+        let mut err = ExternError::default();
+        let code =
+            allosaurus_server_check_non_membership_proof(session, server, ByteArray::from(&proof), 1000, &mut err);
+        assert_ne!(code, 0);
+
+        let mut err = ExternError::default();
+        let mut challenge = ByteBuffer::default();
+        assert_eq!(allosaurus_verifier_issue_challenge(session, 1000, &mut challenge, &mut err), 0);
+        let challenge = challenge.destroy_into_vec();
+        let mut proof = ByteBuffer::default();
+        let code = allosaurus_user_make_non_membership_proof(
+            ByteArray::from(&user),
+            ByteArray::from(&challenge),
+            &mut proof,
+            &mut err,
+        );
+        assert_eq!(code, 0);
+        let proof = proof.destroy_into_vec();
+        let code =
+            allosaurus_server_check_non_membership_proof(session, server, ByteArray::from(&proof), 1001, &mut err);
+        assert_eq!(code, 0);
+
+        // The challenge was redeemed by the first check
+        let mut err = ExternError::default();
+        let code =
+            allosaurus_server_check_non_membership_proof(session, server, ByteArray::from(&proof), 1002, &mut err);
+        assert_ne!(code, 0);
+    }
 }
\ No newline at end of file
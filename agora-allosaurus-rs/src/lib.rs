@@ -13,9 +13,14 @@
     trivial_casts,
     trivial_numeric_casts
 )]
+mod presentation;
+mod rotation;
 mod servers;
+mod service;
 mod user;
 mod utils;
+mod verifier;
+mod wallet;
 mod witness;
 #[cfg(feature = "ffi")]
 mod custom_bytebuffer;
@@ -27,9 +32,14 @@ mod ffi;
 mod mpc;
 
 pub mod accumulator;
+pub use presentation::*;
+pub use rotation::*;
 pub use servers::*;
+pub use service::*;
 pub use user::*;
 pub use utils::*;
+pub use verifier::*;
+pub use wallet::WalletKey;
 pub use witness::*;
 #[cfg(feature = "ffi")]
 pub use ffi::*;
@@ -0,0 +1,168 @@
+use crate::accumulator::{Accumulator, Element, ProofMessage};
+use crate::rotation::KeyLinkage;
+use crate::utils::{AccParams, AccumulatorId, PublicKeys, UserID, SECURITY_BYTES};
+use crate::witness::{MembershipProof, MembershipProofCommitting, Witness};
+use merlin::Transcript;
+use serde::{Deserialize, Serialize};
+
+/// A digest of an accumulator value
+pub type AccumulatorDigest = [u8; 32];
+
+/// Computes the digest a presentation uses to name the accumulator it was made against
+pub fn accumulator_digest(accumulator: &Accumulator) -> AccumulatorDigest {
+    let mut transcript = Transcript::new(b"allosaur_accumulator_digest");
+    transcript.append_message(b"Accumulator", accumulator.0.to_compressed().as_ref());
+    let mut digest = [0u8; 32];
+    transcript.challenge_bytes(b"accumulator digest", &mut digest);
+    digest
+}
+
+/// The state of a registry a verifier trusts, e.g. as published by its server
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccumulatorPublication {
+    /// The registry the accumulator belongs to
+    pub registry_id: String,
+    /// The epoch of the accumulator
+    pub epoch: usize,
+    /// The accumulator value at `epoch`
+    pub accumulator: Accumulator,
+    /// The registry's public keys
+    pub public_keys: PublicKeys,
+}
+
+/// A membership proof bundled with everything a verifier needs to pick the
+/// trusted state to check it against
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NonRevocationPresentation {
+    /// The registry the credential belongs to
+    pub registry_id: String,
+    /// The epoch of the accumulator the proof was made against
+    pub epoch: usize,
+    /// The digest of that accumulator, see [`accumulator_digest`]
+    pub accumulator_digest: AccumulatorDigest,
+    /// The fingerprint of the registry's public keys, see `PublicKeys::accumulator_id`
+    pub public_keys_fingerprint: AccumulatorId,
+    /// The verifier's ephemeral challenge
+    pub challenge: [u8; 2 * SECURITY_BYTES],
+    /// The membership proof, whose transcript commits to all of the above
+    pub proof: MembershipProof,
+}
+
+impl NonRevocationPresentation {
+    /// Proves that `user_id` is in `accumulator`, the accumulator of `epoch`
+    /// in the registry `registry_id`
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        witness: &Witness,
+        user_id: &UserID,
+        accumulator: &Accumulator,
+        epoch: usize,
+        registry_id: &str,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<Self> {
+        let mpc = MembershipProofCommitting::new(
+            witness,
+            ProofMessage::Hidden { message: user_id.0 },
+            ProofMessage::Hidden {
+                message: witness.secret_key.0,
+            },
+            accumulator,
+            params,
+            public_keys,
+        )?;
+
+        let mut presentation = Self {
+            registry_id: registry_id.to_string(),
+            epoch,
+            accumulator_digest: accumulator_digest(accumulator),
+            public_keys_fingerprint: public_keys.accumulator_id(),
+            challenge: *challenge,
+            proof: MembershipProof::default(),
+        };
+        let mut transcript = presentation.transcript();
+        mpc.get_bytes_for_challenge(&mut transcript);
+        transcript.append_message(b"Ephemeral challenge", challenge);
+        let c = Element::from_transcript(b"challenge", &mut transcript);
+        presentation.proof = mpc.gen_proof(c);
+        Some(presentation)
+    }
+
+    /// Verifies the presentation against the trusted state of its registry.
+    ///
+    /// The caller is responsible for checking that `challenge` is one it
+    /// issued, e.g. with `VerifierSession::verify_presentation`
+    pub fn verify(
+        &self,
+        trusted: &AccumulatorPublication,
+        params: &AccParams,
+    ) -> Result<(), &'static str> {
+        if self.registry_id != trusted.registry_id {
+            return Err("presentation is for a different registry");
+        }
+        if self.public_keys_fingerprint != trusted.public_keys.accumulator_id() {
+            return Err("presentation public keys do not match the registry");
+        }
+        if self.epoch != trusted.epoch {
+            return Err("presentation epoch does not match the publication");
+        }
+        if self.accumulator_digest != accumulator_digest(&trusted.accumulator) {
+            return Err("presentation accumulator does not match the publication");
+        }
+
+        let mut transcript = self.transcript();
+        self.proof.get_bytes_for_challenge(
+            params,
+            &trusted.public_keys,
+            &trusted.accumulator,
+            &mut transcript,
+        );
+        transcript.append_message(b"Ephemeral challenge", &self.challenge);
+        let c = Element::from_transcript(b"challenge", &mut transcript);
+        if self.proof.check_challenge(c) {
+            Ok(())
+        } else {
+            Err("invalid membership proof")
+        }
+    }
+
+    /// Verifies the presentation against the current state of its registry,
+    /// or against `old`, the current state under the old keys of the rotation
+    /// in `linkage`, if it was made under them and the grace period has not
+    /// ended. `old` is published by `Server::retired_server`, so holders
+    /// deleted after the rotation no longer verify under the old keys
+    pub fn verify_during_rotation(
+        &self,
+        current: &AccumulatorPublication,
+        old: &AccumulatorPublication,
+        linkage: &KeyLinkage,
+        params: &AccParams,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        if self.public_keys_fingerprint == current.public_keys.accumulator_id() {
+            return self.verify(current, params);
+        }
+        if linkage.new_public_keys.accumulator_id() != current.public_keys.accumulator_id()
+            || linkage.old_public_keys.accumulator_id() != old.public_keys.accumulator_id()
+            || old.registry_id != current.registry_id
+        {
+            return Err("key linkage does not match the registry");
+        }
+        if !linkage.accepts(&linkage.old_public_keys, now) {
+            return Err("grace period has ended");
+        }
+        linkage.verify(params)?;
+        self.verify(old, params)
+    }
+
+    /// Starts the proof transcript with the presentation's context
+    fn transcript(&self) -> Transcript {
+        let mut transcript = Transcript::new(b"non_revocation_presentation");
+        transcript.append_message(b"Registry ID", self.registry_id.as_bytes());
+        transcript.append_u64(b"Epoch", self.epoch as u64);
+        transcript.append_message(b"Accumulator digest", &self.accumulator_digest);
+        transcript.append_message(b"Public keys fingerprint", &self.public_keys_fingerprint);
+        transcript
+    }
+}
@@ -0,0 +1,274 @@
+use crate::accumulator::{Accumulator, Element, MembershipWitness, PublicKey, SecretKey};
+use crate::servers::Server;
+use crate::utils::{AccParams, PublicKeys, UserID};
+use crate::witness::Witness;
+use blsful::inner_types::*;
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+/// A statement that a registry rotated from `old_public_keys` to
+/// `new_public_keys`, signed with all six secret keys.
+///
+/// The signature is a Schnorr proof of knowledge of the old and new witness,
+/// signing and revoked-set secret keys, so it both authenticates the new keys
+/// with the old ones and proves possession of the new ones. Verifiers keep
+/// accepting the old keys until `grace_until`, in seconds since the Unix epoch
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct KeyLinkage {
+    /// The public keys before the rotation
+    pub old_public_keys: PublicKeys,
+    /// The revoked-set public key before the rotation
+    pub old_revoked_key: PublicKey,
+    /// The last accumulator under the old keys
+    pub old_accumulator: Accumulator,
+    /// The epoch of `old_accumulator`
+    pub old_epoch: usize,
+    /// The public keys after the rotation
+    pub new_public_keys: PublicKeys,
+    /// The revoked-set public key after the rotation
+    pub new_revoked_key: PublicKey,
+    /// The first accumulator under the new keys
+    pub new_accumulator: Accumulator,
+    /// The epoch of `new_accumulator`
+    pub new_epoch: usize,
+    /// The end of the grace period for the old keys
+    pub grace_until: u64,
+    /// The Fiat-Shamir challenge
+    pub challenge: Element,
+    /// The responses for the old witness, old signing, new witness,
+    /// new signing, old revoked-set and new revoked-set secret keys
+    pub responses: [Element; 6],
+}
+
+impl KeyLinkage {
+    /// Signs the linkage with the old and new secret keys,
+    /// given in the order of `responses`
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        params: &AccParams,
+        old_public_keys: PublicKeys,
+        old_accumulator: Accumulator,
+        old_epoch: usize,
+        new_public_keys: PublicKeys,
+        new_accumulator: Accumulator,
+        new_epoch: usize,
+        grace_until: u64,
+        secret_keys: [&SecretKey; 6],
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let mut linkage = Self {
+            old_public_keys,
+            old_revoked_key: PublicKey::from(secret_keys[4]),
+            old_accumulator,
+            old_epoch,
+            new_public_keys,
+            new_revoked_key: PublicKey::from(secret_keys[5]),
+            new_accumulator,
+            new_epoch,
+            grace_until,
+            challenge: Element(Scalar::ZERO),
+            responses: [Element(Scalar::ZERO); 6],
+        };
+        let nonces = [(); 6].map(|_| Element::random_with_rng(&mut *rng));
+        let bases = Self::bases(params);
+        let commitments = [0, 1, 2, 3, 4, 5].map(|i| bases[i] * nonces[i].0);
+        linkage.challenge = linkage.compute_challenge(params, &commitments);
+        linkage.responses = [0, 1, 2, 3, 4, 5]
+            .map(|i| Element(nonces[i].0 - linkage.challenge.0 * secret_keys[i].0));
+        linkage
+    }
+
+    /// Verifies the signature of the linkage
+    pub fn verify(&self, params: &AccParams) -> Result<(), &'static str> {
+        let bases = Self::bases(params);
+        let keys = self.keys();
+        let commitments = [0, 1, 2, 3, 4, 5]
+            .map(|i| bases[i] * self.responses[i].0 + keys[i].0 * self.challenge.0);
+        let challenge = self.compute_challenge(params, &commitments);
+        if bool::from(challenge.0.ct_eq(&self.challenge.0)) {
+            Ok(())
+        } else {
+            Err("invalid key linkage")
+        }
+    }
+
+    /// Whether `public_keys` are still accepted at `now`, i.e. they are the
+    /// new keys or the old keys before the end of the grace period
+    pub fn accepts(&self, public_keys: &PublicKeys, now: u64) -> bool {
+        let id = public_keys.accumulator_id();
+        id == self.new_public_keys.accumulator_id()
+            || (now < self.grace_until && id == self.old_public_keys.accumulator_id())
+    }
+
+    /// The generators of the public keys, in the order of `responses`
+    fn bases(params: &AccParams) -> [G2Projective; 6] {
+        [
+            params.get_p2(),
+            params.get_k2(),
+            params.get_p2(),
+            params.get_k2(),
+            G2Projective::GENERATOR,
+            G2Projective::GENERATOR,
+        ]
+    }
+
+    /// The public keys, in the order of `responses`
+    fn keys(&self) -> [PublicKey; 6] {
+        [
+            self.old_public_keys.witness_key,
+            self.old_public_keys.sign_key,
+            self.new_public_keys.witness_key,
+            self.new_public_keys.sign_key,
+            self.old_revoked_key,
+            self.new_revoked_key,
+        ]
+    }
+
+    fn compute_challenge(&self, params: &AccParams, commitments: &[G2Projective; 6]) -> Element {
+        let mut transcript = Transcript::new(b"allosaur_key_linkage");
+        params.add_to_transcript(&mut transcript);
+        for key in self.keys() {
+            transcript.append_message(b"Public key", key.to_bytes().as_ref());
+        }
+        transcript.append_message(b"Old accumulator", self.old_accumulator.to_bytes().as_ref());
+        transcript.append_u64(b"Old epoch", self.old_epoch as u64);
+        transcript.append_message(b"New accumulator", self.new_accumulator.to_bytes().as_ref());
+        transcript.append_u64(b"New epoch", self.new_epoch as u64);
+        transcript.append_u64(b"Grace until", self.grace_until);
+        for commitment in commitments {
+            transcript.append_message(b"commitment", commitment.to_bytes().as_ref());
+        }
+        Element::from_transcript(b"challenge", &mut transcript)
+    }
+}
+
+/// The state a server keeps from before its last rotation until the end of
+/// the grace period, see `Server::rotate_keys`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RetiredKeys {
+    /// The server under the old keys. It keeps receiving the deletions of
+    /// the new one, so holders can still update their old witnesses and
+    /// verifiers check old presentations against its current accumulator
+    pub server: Box<Server>,
+    /// The published linkage between the old and new keys
+    pub linkage: KeyLinkage,
+}
+
+/// A request to re-sign a holder's long-term key under new keys.
+///
+/// The holder reveals their long-term public key U = x * K1 and signature
+/// R = (U + K0) * 1/(s_m + y), which the server saw when issuing them,
+/// and proves knowledge of x
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct MigrationRequest {
+    /// The user ID
+    pub id: UserID,
+    /// The long-term public key U
+    pub public_key: G1Projective,
+    /// The signature R under the old keys
+    pub signature: G1Projective,
+    /// The Fiat-Shamir challenge
+    pub challenge: Element,
+    /// The response for x
+    pub response: Element,
+}
+
+impl MigrationRequest {
+    /// Creates a request to migrate `witness` from `old_public_keys`
+    pub fn new(
+        witness: &Witness,
+        id: &UserID,
+        params: &AccParams,
+        old_public_keys: &PublicKeys,
+    ) -> Self {
+        Self::new_with_rng(witness, id, params, old_public_keys, &mut rand::rngs::OsRng)
+    }
+
+    /// Like `new`, drawing the proof nonce from `rng`
+    pub fn new_with_rng(
+        witness: &Witness,
+        id: &UserID,
+        params: &AccParams,
+        old_public_keys: &PublicKeys,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let public_key = params.get_k1() * witness.secret_key.0;
+        let k = Element::random_with_rng(rng);
+        let commitment = params.get_k1() * k.0;
+        let challenge = Self::challenge(
+            id,
+            old_public_keys,
+            &public_key,
+            &witness.signature,
+            &commitment,
+        );
+        Self {
+            id: *id,
+            public_key,
+            signature: witness.signature,
+            challenge,
+            response: Element(k.0 - challenge.0 * witness.secret_key.0),
+        }
+    }
+
+    /// Verifies the request with the old signing secret key
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        old_public_keys: &PublicKeys,
+        old_sign_secret_key: &SecretKey,
+    ) -> Result<(), &'static str> {
+        // R * (s_m + y) = U + K0
+        let signed = self.signature * (old_sign_secret_key.0 + self.id.0);
+        if signed != self.public_key + params.get_k0() {
+            return Err("invalid migration request");
+        }
+        let commitment = params.get_k1() * self.response.0 + self.public_key * self.challenge.0;
+        let challenge = Self::challenge(
+            &self.id,
+            old_public_keys,
+            &self.public_key,
+            &self.signature,
+            &commitment,
+        );
+        if bool::from(challenge.0.ct_eq(&self.challenge.0)) {
+            Ok(())
+        } else {
+            Err("invalid migration request")
+        }
+    }
+
+    fn challenge(
+        id: &UserID,
+        old_public_keys: &PublicKeys,
+        public_key: &G1Projective,
+        signature: &G1Projective,
+        commitment: &G1Projective,
+    ) -> Element {
+        let mut transcript = Transcript::new(b"user_migration_proof");
+        transcript.append_message(b"User ID", &id.0.to_le_bytes());
+        transcript.append_message(b"Accumulator ID", &old_public_keys.accumulator_id());
+        transcript.append_message(b"user_pub_key", public_key.to_bytes().as_ref());
+        transcript.append_message(b"Signature", signature.to_bytes().as_ref());
+        transcript.append_message(b"commitment", commitment.to_bytes().as_ref());
+        Element::from_transcript(b"challenge", &mut transcript)
+    }
+}
+
+/// A membership witness and long-term signature under the new keys,
+/// issued with `Server::migrate`
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct MigratedWitness {
+    /// The membership witness for `accumulator`
+    pub witness: MembershipWitness,
+    /// The long-term signature under the new signing key
+    pub signature: G1Projective,
+    /// The accumulator the witness is valid for
+    pub accumulator: Accumulator,
+    /// The epoch of the accumulator
+    pub epoch: usize,
+    /// The new public keys
+    pub public_keys: PublicKeys,
+}
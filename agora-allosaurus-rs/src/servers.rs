@@ -1,17 +1,47 @@
 use crate::accumulator::{
-    Accumulator, Element, MembershipWitness, Polynomial, PublicKey, SecretKey,
+    batch_invert, ct_sum_of_products, generate_fr, Accumulator, Coefficient, Element,
+    MembershipWitness, NonMembershipWitness, Polynomial, PublicKey, SecretKey,
 };
-use crate::utils::{AccParams, PublicKeys, UserID};
+use crate::presentation::AccumulatorPublication;
+use crate::rotation::{KeyLinkage, MigratedWitness, MigrationRequest, RetiredKeys};
+use crate::utils::{
+    from_all_bytes, from_versioned_bytes, to_versioned_bytes, AccParams, PublicKeys, UserID,
+};
+use crate::witness::ReissueRequest;
 use blsful::inner_types::*;
 use merlin::Transcript;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, RwLock};
+use subtle::ConstantTimeEq;
+
+/// The salt for deriving the revoked-set secret key of servers
+/// decoded from the legacy layout
+const REVOKED_KEYGEN_SALT: &[u8] = b"ALLOSAUR-REVOKED-KEYGEN-SALT-";
+
+/// The serialization format version written by `Server::to_bytes`
+pub const SERVER_FORMAT_VERSION: u8 = 1;
+
+/// The secret key of the revoked-set accumulator for a legacy server,
+/// which had none, derived from its alpha so decoding is deterministic
+fn derive_revoked_secret_key(alpha: &SecretKey) -> SecretKey {
+    SecretKey(generate_fr(
+        REVOKED_KEYGEN_SALT,
+        Some(&alpha.to_bytes()),
+        rand::rngs::OsRng,
+    ))
+}
 
 /// An ALLOSAUR server
 #[repr(C)]
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Server {
-    /// The managed accumulators
+    /// The managed accumulators, starting from the epoch of `checkpoint`
     pub accumulators: Vec<Accumulator>,
     /// The witness update secret key a.k.a alpha
     pub witness_secret_key: SecretKey,
@@ -23,20 +53,240 @@ pub struct Server {
     pub all_users: HashSet<UserID>,
     /// The set of all witnesses
     pub all_witnesses: HashMap<UserID, MembershipWitness>,
-    /// The list of deletions y_1,...,y_d
+    /// The list of deletions y_1,...,y_d since the epoch of `checkpoint`
     pub deletions: Vec<UserID>,
+    /// The deletions before the epoch of `checkpoint`, which non-membership
+    /// witnesses for the revoked set are still computed over
+    pub pruned_deletions: Vec<UserID>,
+    /// The oldest epoch the server keeps history for, see `Server::prune`
+    pub checkpoint: Checkpoint,
+    /// The secret key for the accumulator of deleted UserIDs
+    pub revoked_secret_key: SecretKey,
+    /// The accumulators of deleted UserIDs, one per epoch
+    pub revoked_accumulators: Vec<Accumulator>,
+    /// The keys from before the last rotation, see `Server::rotate_keys`
+    pub retired_keys: Option<RetiredKeys>,
+    /// The update tables already built for users' update requests
+    #[serde(skip)]
+    pub update_tables: UpdateTableCache,
+}
+
+/// The unversioned layout servers were serialized with before they kept
+/// a checkpoint and a revoked set
+#[derive(Deserialize)]
+struct LegacyServer {
+    accumulators: Vec<Accumulator>,
+    witness_secret_key: SecretKey,
+    public_keys: PublicKeys,
+    sign_secret_key: SecretKey,
+    all_users: HashSet<UserID>,
+    all_witnesses: HashMap<UserID, MembershipWitness>,
+    deletions: Vec<UserID>,
+}
+
+impl TryFrom<LegacyServer> for Server {
+    type Error = &'static str;
+
+    fn try_from(legacy: LegacyServer) -> Result<Self, Self::Error> {
+        // Every deletion moved the accumulator to the next epoch
+        if legacy.accumulators.len() != legacy.deletions.len() + 1 {
+            return Err("Invalid byte sequence");
+        }
+        let revoked_secret_key = derive_revoked_secret_key(&legacy.witness_secret_key);
+        let mut server = Server {
+            checkpoint: Checkpoint::genesis(legacy.accumulators[0]),
+            accumulators: legacy.accumulators,
+            witness_secret_key: legacy.witness_secret_key,
+            public_keys: legacy.public_keys,
+            sign_secret_key: legacy.sign_secret_key,
+            all_users: legacy.all_users,
+            all_witnesses: legacy.all_witnesses,
+            deletions: Vec::new(),
+            pruned_deletions: Vec::new(),
+            revoked_accumulators: vec![Accumulator::with_elements(&revoked_secret_key, &[])],
+            revoked_secret_key,
+            retired_keys: None,
+            update_tables: UpdateTableCache::default(),
+        };
+        // Legacy servers kept their whole history, rebuild the revoked set from it
+        for y in legacy.deletions {
+            server.deletions.push(y);
+            server.add_to_revoked_set(y);
+        }
+        Ok(server)
+    }
+}
+
+/// The precomputed update polynomials for one chunk of deletions
+#[derive(Clone, Debug)]
+struct UpdateTable {
+    /// The coefficients of d(y) = ∏ (y_i - y)
+    d_poly: Vec<Scalar>,
+    /// W_j = ∑_i V_i * v_i[j], the accumulators weighted by the coefficients
+    /// of the v-polynomials
+    v_points: Vec<G1Projective>,
+}
+
+impl UpdateTable {
+    /// The memory the table takes up in an `UpdateTableCache`
+    fn size(&self) -> usize {
+        self.d_poly.len() * size_of::<Scalar>() + self.v_points.len() * size_of::<G1Projective>()
+    }
+}
+
+/// Caches the tables `Server::update` builds for chunks of deletions that
+/// more than one holder requested, so later holders only pay for inner
+/// products. Chunks are keyed by their absolute deletion indices.
+///
+/// Deletions are append-only, so a table never goes stale. The cache is
+/// cleared once it would use more than `UPDATE_TABLE_CACHE_BYTES`, and is
+/// not serialized or cloned with the server
+#[derive(Default)]
+pub struct UpdateTableCache(RwLock<CachedTables>);
+
+#[derive(Default)]
+struct CachedTables {
+    tables: HashMap<(usize, usize), Arc<UpdateTable>>,
+    /// Chunks requested once, which get a table when requested again
+    requested: HashSet<(usize, usize)>,
+    bytes: usize,
+}
+
+/// The memory an `UpdateTableCache` may use before it is cleared
+pub const UPDATE_TABLE_CACHE_BYTES: usize = 64 << 20;
+
+/// The most deletions `Server::update` answers with one chunk.
+/// Larger chunks would make a table miss cost more than the holder saves
+pub const MAX_UPDATE_CHUNK: usize = 256;
+
+impl UpdateTableCache {
+    /// The number of cached chunks
+    pub fn len(&self) -> usize {
+        self.0.read().map_or(0, |cache| cache.tables.len())
+    }
+
+    /// Whether no chunks are cached
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The memory used by the cached chunks
+    pub fn size(&self) -> usize {
+        self.0.read().map_or(0, |cache| cache.bytes)
+    }
+
+    /// Drops every cached chunk
+    pub fn clear(&self) {
+        if let Ok(mut cache) = self.0.write() {
+            *cache = CachedTables::default();
+        }
+    }
+
+    fn get(&self, key: (usize, usize)) -> Option<Arc<UpdateTable>> {
+        self.0.read().ok()?.tables.get(&key).cloned()
+    }
+
+    /// Records a request for a chunk without a table,
+    /// returning whether it was requested before
+    fn request(&self, key: (usize, usize)) -> bool {
+        // A poisoned lock only means the cache is not used
+        let Ok(mut cache) = self.0.write() else {
+            return false;
+        };
+        if cache.requested.remove(&key) {
+            return true;
+        }
+        cache.bytes += size_of::<(usize, usize)>();
+        if cache.bytes > UPDATE_TABLE_CACHE_BYTES {
+            *cache = CachedTables::default();
+        }
+        cache.requested.insert(key);
+        false
+    }
+
+    fn insert(&self, key: (usize, usize), table: Arc<UpdateTable>) {
+        if let Ok(mut cache) = self.0.write() {
+            cache.bytes += table.size();
+            if cache.bytes > UPDATE_TABLE_CACHE_BYTES {
+                *cache = CachedTables::default();
+                cache.bytes = table.size();
+            }
+            cache.tables.insert(key, table);
+        }
+    }
+}
+
+impl Clone for UpdateTableCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Debug for UpdateTableCache {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "UpdateTableCache {{ {} chunks }}", self.len())
+    }
+}
+
+impl Debug for Server {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("accumulators", &self.accumulators)
+            .field("witness_secret_key", &"<redacted>")
+            .field("public_keys", &self.public_keys)
+            .field("sign_secret_key", &"<redacted>")
+            .field("all_users", &self.all_users)
+            .field("all_witnesses", &self.all_witnesses)
+            .field("deletions", &self.deletions)
+            .field("pruned_deletions", &self.pruned_deletions)
+            .field("checkpoint", &self.checkpoint)
+            .field("revoked_secret_key", &"<redacted>")
+            .field("revoked_accumulators", &self.revoked_accumulators)
+            .field("retired_keys", &self.retired_keys)
+            .field("update_tables", &self.update_tables)
+            .finish()
+    }
 }
 
 impl Server {
     /// Creates a new server with random parameters
     pub fn new(params: &AccParams) -> Server {
-        let alpha = SecretKey::new(None);
-        let s_m = SecretKey::new(None);
+        Self::from_rng(params, &mut rand::rngs::OsRng)
+    }
+
+    /// Creates a new server with keys and initial accumulator drawn from `rng`
+    pub fn from_rng(params: &AccParams, rng: &mut (impl RngCore + CryptoRng)) -> Server {
+        let alpha = SecretKey::random_with_rng(&mut *rng);
+        let s_m = SecretKey::random_with_rng(&mut *rng);
+        let v = params.get_p1() * Element::random_with_rng(&mut *rng).0;
+        let revoked_secret_key = SecretKey::random_with_rng(&mut *rng);
+        Self::from_keys(params, alpha, s_m, revoked_secret_key, Accumulator(v))
+    }
+
+    /// Deterministically creates a server from `seed`, which must be kept
+    /// as secret as the keys it produces
+    pub fn from_seed(params: &AccParams, seed: [u8; 32]) -> Server {
+        Self::from_rng(params, &mut ChaCha20Rng::from_seed(seed))
+    }
+
+    /// Creates a server from backed-up key material: the witness secret key
+    /// alpha, the signing secret key s_m, the secret key of the revoked-set
+    /// accumulator and the initial accumulator.
+    /// `rotate_keys` replaces all three keys, so they must be backed up
+    /// again after each rotation
+    pub fn from_keys(
+        params: &AccParams,
+        alpha: SecretKey,
+        s_m: SecretKey,
+        revoked_secret_key: SecretKey,
+        initial_accumulator: Accumulator,
+    ) -> Server {
         let q = params.get_p2() * alpha.0;
         let q_m = params.get_k2() * s_m.0;
-        let v = params.get_p1() * SecretKey::new(None).0;
+        let revoked_accumulator = Accumulator::with_elements(&revoked_secret_key, &[]);
         Server {
-            accumulators: vec![Accumulator(v)],
+            accumulators: vec![initial_accumulator],
+            checkpoint: Checkpoint::genesis(initial_accumulator),
             witness_secret_key: alpha,
             sign_secret_key: s_m,
             public_keys: PublicKeys {
@@ -46,6 +296,11 @@ impl Server {
             all_users: HashSet::new(),
             all_witnesses: HashMap::new(),
             deletions: Vec::new(),
+            pruned_deletions: Vec::new(),
+            revoked_secret_key,
+            revoked_accumulators: vec![revoked_accumulator],
+            retired_keys: None,
+            update_tables: UpdateTableCache::default(),
         }
     }
 
@@ -54,13 +309,11 @@ impl Server {
         if self.all_witnesses.contains_key(&y) {
             return None;
         }
+        // Create a new witness, the inversion only fails if y = -alpha
+        let inv = Option::<Scalar>::from((y.0 + self.witness_secret_key.0).invert())?;
+        let wit = MembershipWitness(self.accumulators.last()?.0 * inv);
         // Add to set of accumulated elements
         self.all_users.insert(y);
-        // Create a new witness
-        let wit = MembershipWitness(
-            self.accumulators.last().unwrap().0
-                * (y.0 + self.witness_secret_key.0).invert().unwrap(),
-        );
         // Keep track of all witnesses
         self.all_witnesses.insert(y, wit);
         // In the MPC setting all servers would run this check
@@ -70,29 +323,75 @@ impl Server {
         Some(wit)
     }
 
+    /// Adds many new elements at once, inverting all of their witness
+    /// denominators together. Fails without adding anything if an element
+    /// was already added, appears twice, or equals the negated secret key
+    pub fn add_batch(&mut self, ys: &[UserID]) -> Result<Vec<MembershipWitness>, &'static str> {
+        let mut seen = HashSet::with_capacity(ys.len());
+        if ys
+            .iter()
+            .any(|y| self.all_witnesses.contains_key(y) || !seen.insert(*y))
+        {
+            return Err("user has already been added");
+        }
+        let mut inverses: Vec<Scalar> =
+            ys.iter().map(|y| y.0 + self.witness_secret_key.0).collect();
+        batch_invert(&mut inverses)?;
+        let accumulator = self.get_accumulator();
+        let witnesses: Vec<MembershipWitness> = inverses
+            .iter()
+            .map(|inv| MembershipWitness(accumulator.0 * inv))
+            .collect();
+        for (y, wit) in ys.iter().zip(witnesses.iter()) {
+            self.all_users.insert(*y);
+            self.all_witnesses.insert(*y, *wit);
+        }
+        Ok(witnesses)
+    }
+
     /// Deletes an element by using the built-in array
     /// When the number of users is large this is SLOW
     /// While it conforms to the specification, likely an improvement
     /// will be to keep an epoch with each witness and run a batch update
     /// when the witness is needed for a deletion
     pub fn delete(&mut self, user_id: UserID) -> Option<Accumulator> {
-        match self.all_witnesses.remove(&user_id) {
-            None => None,
-            Some(wit) => {
-                let new_accumulator = Accumulator(wit.0);
-                self.accumulators.push(new_accumulator);
-
-                // Update all witnesses for the new accumulator
-                for (other_y, other_witness) in self.all_witnesses.iter_mut() {
-                    // (C - V') * (1 / {y - y'})
-                    let t = (other_witness.0 - new_accumulator.0)
-                        * (user_id.0 - other_y.0).invert().expect("to not be zero");
-                    other_witness.0 = t;
-                }
-                self.deletions.push(user_id);
-                Some(new_accumulator)
-            }
+        let wit = self.all_witnesses.remove(&user_id)?;
+        let new_accumulator = Accumulator(wit.0);
+
+        // Update all witnesses for the new accumulator
+        let mut witnesses: Vec<(&UserID, &mut MembershipWitness)> =
+            self.all_witnesses.iter_mut().collect();
+        // 1 / {y - y'} for every other user, with one inversion
+        let mut inverses: Vec<Scalar> = witnesses
+            .iter()
+            .map(|(other_y, _)| user_id.0 - other_y.0)
+            .collect();
+        if batch_invert(&mut inverses).is_err() {
+            // Only possible if the witness map is inconsistent; leave it untouched
+            self.all_witnesses.insert(user_id, wit);
+            return None;
         }
+        let update_witness =
+            |((_, other_witness), inv): (&mut (&UserID, &mut MembershipWitness), &Scalar)| {
+                // (C - V') * (1 / {y - y'})
+                other_witness.0 = (other_witness.0 - new_accumulator.0) * inv;
+            };
+        #[cfg(feature = "parallel")]
+        witnesses
+            .par_iter_mut()
+            .zip(inverses.par_iter())
+            .for_each(update_witness);
+        #[cfg(not(feature = "parallel"))]
+        witnesses
+            .iter_mut()
+            .zip(inverses.iter())
+            .for_each(update_witness);
+
+        self.accumulators.push(new_accumulator);
+        self.deletions.push(user_id);
+        self.add_to_revoked_set(user_id);
+        self.delete_retired(user_id);
+        Some(new_accumulator)
     }
 
     /// Uses the secret key to quickly delete an element
@@ -101,21 +400,79 @@ impl Server {
         if !self.all_witnesses.contains_key(&y) {
             return None;
         }
+        let inv = Option::<Scalar>::from((y.0 + self.witness_secret_key.0).invert())?;
         self.all_witnesses.remove(&y);
 
-        let new_accumulator = Accumulator(
-            self.accumulators.last().expect("at least one element").0
-                * (y.0 + self.witness_secret_key.0)
-                    .invert()
-                    .expect("to not be zero"),
-        );
+        let new_accumulator = Accumulator(self.accumulators.last()?.0 * inv);
         self.accumulators.push(new_accumulator);
         // Update all witnesses for the new accumulator
 
         self.deletions.push(y);
+        self.add_to_revoked_set(y);
+        self.delete_retired(y);
         Some(new_accumulator)
     }
 
+    /// Adds a deleted UserID to the revoked-set accumulator,
+    /// keeping it in step with the epochs of the main accumulator
+    fn add_to_revoked_set(&mut self, y: UserID) {
+        let revoked_accumulator = self
+            .get_revoked_accumulator()
+            .add(&self.revoked_secret_key, y);
+        self.revoked_accumulators.push(revoked_accumulator);
+    }
+
+    /// Deletes a UserID from the server under the old keys as well,
+    /// so it no longer verifies under them during the grace period
+    fn delete_retired(&mut self, y: UserID) {
+        if let Some(retired) = self.retired_keys.as_mut() {
+            // Members added after the rotation are not in the old accumulator
+            retired.server.quick_delete(y);
+        }
+    }
+
+    /// Issues a witness that the ID of `request` is not in the revoked set,
+    /// after checking that the requester holds a signature for it
+    pub fn non_membership_witness(
+        &self,
+        params: &AccParams,
+        request: &ReissueRequest,
+    ) -> Result<NonMembershipWitness, &'static str> {
+        request.verify(params, &self.public_keys, &self.sign_secret_key)?;
+        let deletions: Vec<UserID> = self
+            .pruned_deletions
+            .iter()
+            .chain(self.deletions.iter())
+            .copied()
+            .collect();
+        NonMembershipWitness::new(request.id, &deletions, &self.revoked_secret_key)
+            .ok_or("user has been deleted")
+    }
+
+    /// Publishes the revoked-set updates for every deletion after `epoch`,
+    /// which holders apply to their non-membership witnesses
+    pub fn publish_revoked_set_updates_since(
+        &self,
+        epoch: usize,
+    ) -> Result<Vec<RevokedSetUpdate>, &'static str> {
+        if epoch == 0 || epoch > self.get_epoch() {
+            return Err("invalid epoch");
+        }
+        let first_epoch = self.first_epoch();
+        if epoch < first_epoch {
+            return Err("re-issue required");
+        }
+        // The deletion at index i moves the revoked set from epoch
+        // first_epoch+i to first_epoch+i+1
+        Ok((epoch - first_epoch..self.deletions.len())
+            .map(|i| RevokedSetUpdate {
+                epoch: first_epoch + i,
+                deletion: self.deletions[i],
+                accumulator: self.revoked_accumulators[i + 1],
+            })
+            .collect())
+    }
+
     /// Given a user ID y and a signature proof (via challenge and response),
     /// returns (C,R) such that C is a witness for y and R is a long-term
     /// signature
@@ -141,91 +498,431 @@ impl Server {
                 .as_ref(),
         );
         let check = Element::from_transcript(b"challenge", &mut transcript);
-        if check != *challenge {
+        if !bool::from(check.0.ct_eq(&challenge.0)) {
             return None;
         }
         // Look up witness (could compute as needed, but lookup is better for MPC version)
         let acc_witness = self.all_witnesses[y];
         // Sign y and (user_pub_key + K0) using the signing secret key
-        let signature = (user_pub_key + params.get_k0())
-            * ((y.0 + self.sign_secret_key.0)
-                .invert()
-                .expect("to not be zero"));
+        let inv = Option::<Scalar>::from((y.0 + self.sign_secret_key.0).invert())?;
+        let signature = (user_pub_key + params.get_k0()) * inv;
         Some((acc_witness, signature))
     }
 
+    /// Re-issues a current membership witness to a holder who proved
+    /// possession of the long-term key and signature issued for their ID
+    pub fn reissue(
+        &self,
+        params: &AccParams,
+        request: &ReissueRequest,
+    ) -> Result<ReissuedWitness, &'static str> {
+        request.verify(params, &self.public_keys, &self.sign_secret_key)?;
+        self.reissue_unchecked(&request.id)
+    }
+
+    /// Issues a current membership witness for `id` without checking
+    /// that the requester holds a signature for it
+    fn reissue_unchecked(&self, id: &UserID) -> Result<ReissuedWitness, &'static str> {
+        if !self.all_witnesses.contains_key(id) {
+            return Err("user is not a member");
+        }
+        // Computed from the secret key, since `quick_delete` leaves the
+        // stored witnesses behind
+        let accumulator = self.get_accumulator();
+        let witness = MembershipWitness::new(*id, accumulator, &self.witness_secret_key)
+            .ok_or("unable to create witness")?;
+        Ok(ReissuedWitness {
+            witness,
+            accumulator,
+            epoch: self.get_epoch(),
+        })
+    }
+
+    /// Rotates the witness, signing and revoked-set secret keys.
+    ///
+    /// Starts a new accumulator under the new keys in the next epoch and
+    /// recomputes the witnesses of all current members. The revoked set is
+    /// rebuilt under the new key, so holders need new non-membership witnesses.
+    /// Returns the signed linkage to publish to verifiers.
+    ///
+    /// Until `grace_until`, holders move to the new keys with
+    /// `User::migrate_witness`, and verifiers may still accept presentations
+    /// under the old keys against `retired_server`, which keeps the old
+    /// history and receives every later deletion. Both trust the old keys,
+    /// so after a compromise pass a `grace_until` in the past and issue
+    /// holders new witnesses instead
+    pub fn rotate_keys(
+        &mut self,
+        params: &AccParams,
+        grace_until: u64,
+    ) -> Result<KeyLinkage, &'static str> {
+        self.rotate_keys_with_rng(params, grace_until, &mut rand::rngs::OsRng)
+    }
+
+    /// Like `rotate_keys`, drawing the new keys, accumulator and linkage
+    /// signature nonces from `rng`
+    pub fn rotate_keys_with_rng(
+        &mut self,
+        params: &AccParams,
+        grace_until: u64,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<KeyLinkage, &'static str> {
+        let alpha = SecretKey::random_with_rng(&mut *rng);
+        let s_m = SecretKey::random_with_rng(&mut *rng);
+        let revoked_secret_key = SecretKey::random_with_rng(&mut *rng);
+        let public_keys = PublicKeys {
+            witness_key: PublicKey(params.get_p2() * alpha.0),
+            sign_key: PublicKey(params.get_k2() * s_m.0),
+        };
+        let accumulator = Accumulator(params.get_p1() * Element::random_with_rng(&mut *rng).0);
+
+        let ids: Vec<UserID> = self.all_witnesses.keys().copied().collect();
+        let mut inverses: Vec<Scalar> = ids.iter().map(|y| y.0 + alpha.0).collect();
+        batch_invert(&mut inverses)?;
+
+        let old_epoch = self.get_epoch();
+        let linkage = KeyLinkage::new(
+            params,
+            self.public_keys,
+            self.get_accumulator(),
+            old_epoch,
+            public_keys,
+            accumulator,
+            old_epoch + 1,
+            grace_until,
+            [
+                &self.witness_secret_key,
+                &self.sign_secret_key,
+                &alpha,
+                &s_m,
+                &self.revoked_secret_key,
+                &revoked_secret_key,
+            ],
+            rng,
+        );
+
+        // Only the server under the old keys keeps their history
+        self.retired_keys = None;
+        let retired = Box::new(self.clone());
+        self.prune(1)?;
+        self.checkpoint = self.checkpoint.rotate(&public_keys, &accumulator);
+        self.accumulators = vec![accumulator];
+        // The inverses depend on alpha, so each witness is computed with
+        // a plain constant-time scalar multiplication
+        self.all_witnesses = ids
+            .into_iter()
+            .zip(inverses.iter())
+            .map(|(y, inv)| (y, MembershipWitness(accumulator.0 * inv)))
+            .collect();
+        self.revoked_accumulators = vec![Accumulator::with_elements(
+            &revoked_secret_key,
+            &self.pruned_deletions,
+        )];
+        self.revoked_secret_key = revoked_secret_key;
+        self.sign_secret_key = s_m;
+        self.witness_secret_key = alpha;
+        self.public_keys = public_keys;
+        self.retired_keys = Some(RetiredKeys {
+            server: retired,
+            linkage,
+        });
+        self.update_tables.clear();
+        Ok(linkage)
+    }
+
+    /// The server under the keys before the last rotation, which holders of
+    /// old witnesses update against and verifiers publish the old accumulator
+    /// of during the grace period
+    pub fn retired_server(&self) -> Option<&Server> {
+        Some(&self.retired_keys.as_ref()?.server)
+    }
+
+    /// Drops the state kept from before the last rotation, including the old
+    /// secret keys, once its grace period has ended
+    pub fn end_grace_period(&mut self) {
+        self.retired_keys = None;
+    }
+
+    /// Issues a membership witness and long-term signature under the new
+    /// keys to a holder of a signature under the keys before the last
+    /// rotation. Migration is only possible until the end of the grace period
+    pub fn migrate(
+        &self,
+        params: &AccParams,
+        request: &MigrationRequest,
+        now: u64,
+    ) -> Result<MigratedWitness, &'static str> {
+        let retired = self.retired_keys.as_ref().ok_or("no key rotation")?;
+        if now >= retired.linkage.grace_until {
+            return Err("grace period has ended");
+        }
+        request.verify(
+            params,
+            &retired.linkage.old_public_keys,
+            &retired.server.sign_secret_key,
+        )?;
+        let reissued = self.reissue_unchecked(&request.id)?;
+        let inv = Option::<Scalar>::from((request.id.0 + self.sign_secret_key.0).invert())
+            .ok_or("unable to sign")?;
+        Ok(MigratedWitness {
+            witness: reissued.witness,
+            signature: (request.public_key + params.get_k0()) * inv,
+            accumulator: reissued.accumulator,
+            epoch: reissued.epoch,
+            public_keys: self.public_keys,
+        })
+    }
+
     /// Given shares from a user, returns the array of (d,W) which can each be used as
     /// C <- (C - W)*(1/d)
-    /// for an update
+    /// for an update.
+    ///
+    /// Chunks hold a power of two of at most `MAX_UPDATE_CHUNK` deletions,
+    /// no more than the user's shares allow, and start at multiples of their
+    /// size in the whole deletion history, so holders at different epochs
+    /// share chunks.
+    /// Returns an error if the server no longer keeps the last `num_epochs` deletions
     pub fn update(
         &self,
         num_epochs: usize,
         y_shares: &[Scalar],
-    ) -> (Vec<Scalar>, Vec<G1Projective>) {
-        // If user requests more updates than possible
+    ) -> Result<(Vec<Scalar>, Vec<G1Projective>), &'static str> {
+        // If user requests more updates than the server keeps
         if num_epochs > self.deletions.len() {
-            return (Vec::new(), Vec::new());
+            return Err("re-issue required");
+        }
+        if y_shares.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
         }
 
-        // Degree of user shares
-        let k = y_shares.len() + 1;
-
-        // The arrays to return
-        let mut ds = Vec::with_capacity(self.deletions.len());
-        let mut vs = Vec::with_capacity(self.deletions.len());
+        // Deletions per chunk
+        let size = y_shares.len().min(MAX_UPDATE_CHUNK);
+        let size = 1 << (usize::BITS - 1 - size.leading_zeros());
 
         let n_del = self.deletions.len();
-        let n_acc = self.accumulators.len();
-
-        // Index of updates to build arrays
-        let mut del_start = n_del - num_epochs;
-        let mut acc_start = n_acc - num_epochs;
+        // The index of deletions[0] in the whole deletion history
+        let offset = self.first_epoch() - 1;
+        let mut chunks = Vec::new();
+        let mut start = n_del - num_epochs;
+        while start < n_del {
+            let end = (((offset + start) / size + 1) * size - offset).min(n_del);
+            chunks.push((start, end));
+            start = end;
+        }
 
-        let m1 = -Scalar::ONE;
+        // Evaluates the update for the chunk of deletions start..end
+        let update_chunk = |&(start, end): &(usize, usize)| -> (Scalar, G1Projective) {
+            let key = (offset + start, offset + end);
+            let table = match self.update_tables.get(key) {
+                Some(table) => table,
+                None if self.update_tables.request(key) => {
+                    let table = Arc::new(self.update_table(start, end));
+                    self.update_tables.insert(key, table.clone());
+                    table
+                }
+                None => return self.evaluate_chunk(start, end, y_shares),
+            };
 
-        // Iterate over all updates in chunks of size k
-        while del_start < n_del {
-            let mut d_poly = Polynomial::default();
-            let mut v_polys: Vec<Polynomial> = Vec::new();
-            d_poly.push(Scalar::ONE);
-            // Create the update polynomials
-            for i in del_start..std::cmp::min(del_start + k - 1, n_del) {
-                v_polys.push(d_poly.clone());
-                d_poly *= &[self.deletions[i].0, m1];
+            // Evalute d_poly
+            let mut d = table.d_poly[0];
+            for i in 1..table.d_poly.len() {
+                d += table.d_poly[i] * y_shares[i - 1];
             }
 
-            // Evalute d_poly
-            let mut d = d_poly.0[0];
-            for i in 1..d_poly.0.len() {
-                d += d_poly.0[i] * y_shares[i - 1];
+            // Evaluate the v-polynomial on accumulator points,
+            // in constant time since the shares are secret
+            let n = table.v_points.len();
+            let mut v = table.v_points[0];
+            if n > 1 {
+                v += ct_sum_of_products(&table.v_points[1..], &y_shares[..n - 1]);
             }
-            ds.push(d);
-
-            // Evaluate all v polys
-            let mut v_poly_evals = vec![Scalar::ZERO; v_polys.len()];
-            for (i, v) in v_polys.iter().enumerate() {
-                v_poly_evals[i] = v.0[0];
-                for ii in 1..v.0.len() {
-                    v_poly_evals[i] += v.0[ii] * y_shares[ii - 1];
+            (d, v)
+        };
+
+        #[cfg(feature = "parallel")]
+        let updates: Vec<(Scalar, G1Projective)> = chunks.par_iter().map(update_chunk).collect();
+        #[cfg(not(feature = "parallel"))]
+        let updates: Vec<(Scalar, G1Projective)> = chunks.iter().map(update_chunk).collect();
+        Ok(updates.into_iter().unzip())
+    }
+
+    /// The update polynomials d and v_i for deletions `start..end`
+    fn update_polynomials(&self, start: usize, end: usize) -> (Polynomial, Vec<Polynomial>) {
+        let m1 = -Scalar::ONE;
+        let mut d_poly = Polynomial::default();
+        let mut v_polys: Vec<Polynomial> = Vec::new();
+        d_poly.push(Scalar::ONE);
+        for i in start..end {
+            v_polys.push(d_poly.clone());
+            d_poly *= &[self.deletions[i].0, m1];
+        }
+        (d_poly, v_polys)
+    }
+
+    /// Evaluates the update for deletions `start..end` without a table,
+    /// which costs field multiplications and a single inner product
+    fn evaluate_chunk(
+        &self,
+        start: usize,
+        end: usize,
+        y_shares: &[Scalar],
+    ) -> (Scalar, G1Projective) {
+        let (d_poly, v_polys) = self.update_polynomials(start, end);
+
+        // Evalute d_poly
+        let mut d = d_poly.0[0];
+        for i in 1..d_poly.0.len() {
+            d += d_poly.0[i] * y_shares[i - 1];
+        }
+
+        // Evaluate all v polys
+        let v_poly_evals: Vec<Scalar> = v_polys
+            .iter()
+            .map(|v| {
+                let mut eval = v.0[0];
+                for i in 1..v.0.len() {
+                    eval += v.0[i] * y_shares[i - 1];
                 }
-            }
+                eval
+            })
+            .collect();
 
-            // Evaluate the v-polynomial on accumulator points
-            let mut v_point = G1Projective::IDENTITY;
-            for (i, v) in v_poly_evals.iter().enumerate() {
-                v_point += self.accumulators[acc_start + i].0 * v;
-            }
-            vs.push(v_point);
+        // Evaluate the v-polynomial on accumulator points.
+        // The accumulator after deletion i is accumulators[i + 1]
+        let acc_start = start + self.accumulators.len() - self.deletions.len();
+        let points: Vec<G1Projective> = self.accumulators[acc_start..acc_start + v_polys.len()]
+            .iter()
+            .map(|a| a.0)
+            .collect();
+        (d, ct_sum_of_products(&points, &v_poly_evals))
+    }
+
+    /// Builds the update table for deletions `start..end`
+    fn update_table(&self, start: usize, end: usize) -> UpdateTable {
+        let (d_poly, v_polys) = self.update_polynomials(start, end);
+
+        // Weight the accumulators by the v-polynomial coefficients, so that
+        // evaluating at the user's shares is a single inner product.
+        // The accumulator after deletion i is accumulators[i + 1]
+        let acc_start = start + self.accumulators.len() - self.deletions.len();
+        let v_points = (0..v_polys.len())
+            .map(|j| {
+                let (points, scalars): (Vec<G1Projective>, Vec<Scalar>) = v_polys
+                    .iter()
+                    .enumerate()
+                    .skip(j)
+                    .map(|(i, v)| (self.accumulators[acc_start + i].0, v.0[j]))
+                    .unzip();
+                G1Projective::sum_of_products(&points, &scalars)
+            })
+            .collect();
+
+        UpdateTable {
+            d_poly: d_poly.0,
+            v_points,
+        }
+    }
+
+    /// Publishes the whole deletion history as VB batch updates of at most
+    /// `slice_size` deletions each, starting from the first retained epoch.
+    /// This is the single-server alternative to the MPC `update`
+    pub fn publish_split_updates(
+        &self,
+        slice_size: usize,
+    ) -> Result<Vec<SplitUpdate>, &'static str> {
+        self.publish_split_updates_since(self.first_epoch(), slice_size)
+    }
+
+    /// Publishes VB batch updates of at most `slice_size` deletions each
+    /// for every deletion after `epoch`
+    pub fn publish_split_updates_since(
+        &self,
+        epoch: usize,
+        slice_size: usize,
+    ) -> Result<Vec<SplitUpdate>, &'static str> {
+        if slice_size == 0 {
+            return Err("invalid slice size");
+        }
+        if epoch == 0 || epoch > self.get_epoch() {
+            return Err("invalid epoch");
+        }
+        let first_epoch = self.first_epoch();
+        if epoch < first_epoch {
+            return Err("re-issue required");
+        }
+        // The deletion at index i moves the accumulator from epoch
+        // first_epoch+i to first_epoch+i+1
+        let first = epoch - first_epoch;
+        let updates = self.deletions[first..]
+            .chunks(slice_size)
+            .enumerate()
+            .map(|(i, slice)| {
+                let start = first + i * slice_size;
+                let (accumulator, coefficients) =
+                    self.accumulators[start].update(&self.witness_secret_key, &[], slice);
+                SplitUpdate {
+                    epoch: first_epoch + start,
+                    deletions: slice.to_vec(),
+                    coefficients,
+                    accumulator,
+                }
+            })
+            .collect();
+        Ok(updates)
+    }
+
+    /// Serializes this server, prefixed with `SERVER_FORMAT_VERSION`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        to_versioned_bytes(SERVER_FORMAT_VERSION, self)
+    }
 
-            del_start += k - 1;
-            acc_start += k - 1;
+    /// Deserializes a server written by `to_bytes`, or by earlier
+    /// versions of this crate without a version byte
+    pub fn from_bytes(input: &[u8]) -> Result<Self, &'static str> {
+        match from_versioned_bytes(SERVER_FORMAT_VERSION, input) {
+            Some(server) => Ok(server),
+            None => from_all_bytes::<LegacyServer>(input)
+                .ok_or("Invalid byte sequence")?
+                .try_into(),
         }
-        (ds, vs)
     }
 
     /// The latest epoch of the accumulator
     pub fn get_epoch(&self) -> usize {
-        self.accumulators.len()
+        self.checkpoint.epoch + self.accumulators.len() - 1
+    }
+
+    /// The oldest epoch the server can still update witnesses from.
+    /// Holders of older witnesses must be re-issued one
+    pub fn first_epoch(&self) -> usize {
+        self.checkpoint.epoch
+    }
+
+    /// Prunes the history older than the last `retain_epochs` epochs and
+    /// returns the checkpoint that replaces it.
+    ///
+    /// The checkpoint's digest commits to every pruned accumulator and
+    /// deletion, so the history can still be audited against it with
+    /// `Checkpoint::advance` and `Checkpoint::rotate`
+    pub fn prune(&mut self, retain_epochs: usize) -> Result<Checkpoint, &'static str> {
+        if retain_epochs == 0 {
+            return Err("must retain at least one epoch");
+        }
+        let n = self.accumulators.len().saturating_sub(retain_epochs);
+        if n == 0 {
+            return Ok(self.checkpoint);
+        }
+        for i in 0..n {
+            self.checkpoint = self
+                .checkpoint
+                .advance(&self.deletions[i], &self.accumulators[i + 1]);
+        }
+        self.accumulators.drain(..n);
+        self.revoked_accumulators.drain(..n);
+        self.pruned_deletions.extend(self.deletions.drain(..n));
+        // Holders behind the checkpoint are re-issued, so their chunks are not requested again
+        self.update_tables.clear();
+        Ok(self.checkpoint)
     }
 
     /// Get the most recent accumulator
@@ -233,6 +930,26 @@ impl Server {
         *(self.accumulators.last().unwrap())
     }
 
+    /// Get the accumulator of an earlier epoch
+    pub fn get_accumulator_at(&self, epoch: usize) -> Option<Accumulator> {
+        self.accumulators
+            .get(epoch.checked_sub(self.first_epoch())?)
+            .copied()
+    }
+
+    /// Publishes the `(epoch, accumulator)` pairs of the last `window`
+    /// retained epochs, which verifiers check epoch membership proofs against
+    pub fn publish_accumulator_history(&self, window: usize) -> Vec<(usize, Accumulator)> {
+        let first = self.first_epoch();
+        let skip = self.accumulators.len().saturating_sub(window);
+        self.accumulators
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .map(|(i, accumulator)| (first + i, *accumulator))
+            .collect()
+    }
+
     /// Get the witness public key
     pub fn get_witness_public_key(&self) -> PublicKey {
         self.public_keys.witness_key
@@ -243,8 +960,126 @@ impl Server {
         self.public_keys.sign_key
     }
 
+    /// Publishes the current accumulator of registry `registry_id` for verifiers
+    pub fn publish(&self, registry_id: &str) -> AccumulatorPublication {
+        AccumulatorPublication {
+            registry_id: registry_id.to_string(),
+            epoch: self.get_epoch(),
+            accumulator: self.get_accumulator(),
+            public_keys: self.public_keys,
+        }
+    }
+
     /// Get the secret key for the witness
     pub fn get_public_keys(&self) -> PublicKeys {
         self.public_keys
     }
+
+    /// Get the most recent accumulator of deleted UserIDs
+    pub fn get_revoked_accumulator(&self) -> Accumulator {
+        *(self.revoked_accumulators.last().unwrap())
+    }
+
+    /// Get the public key of the revoked-set accumulator
+    pub fn get_revoked_public_key(&self) -> PublicKey {
+        PublicKey::from(&self.revoked_secret_key)
+    }
+}
+
+/// A membership witness re-issued with `Server::reissue`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReissuedWitness {
+    /// The membership witness for the current accumulator
+    pub witness: MembershipWitness,
+    /// The accumulator the witness is valid for
+    pub accumulator: Accumulator,
+    /// The epoch of the accumulator
+    pub epoch: usize,
+}
+
+/// A digest of the accumulator history, see [`Checkpoint`]
+pub type HistoryDigest = [u8; 32];
+
+/// A compact record of the history a server pruned: the first epoch it
+/// still keeps, the accumulator of that epoch and a cumulative digest of
+/// every accumulator and deletion before it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The epoch of the checkpoint
+    pub epoch: usize,
+    /// The accumulator at `epoch`
+    pub accumulator: Accumulator,
+    /// The digest of the history up to `epoch`
+    pub digest: HistoryDigest,
+}
+
+impl Checkpoint {
+    /// The checkpoint of a server's first epoch, with an empty history
+    pub fn genesis(accumulator: Accumulator) -> Self {
+        Self {
+            epoch: 1,
+            accumulator,
+            digest: [0u8; 32],
+        }
+    }
+
+    /// The checkpoint of the next epoch, after `deletion` moved the
+    /// accumulator to `accumulator`
+    pub fn advance(&self, deletion: &UserID, accumulator: &Accumulator) -> Self {
+        let mut transcript = Transcript::new(b"allosaur_history_digest");
+        transcript.append_message(b"Previous digest", &self.digest);
+        transcript.append_u64(b"Epoch", self.epoch as u64);
+        transcript.append_message(b"Accumulator", self.accumulator.0.to_compressed().as_ref());
+        transcript.append_message(b"Deletion", &deletion.0.to_le_bytes());
+        let mut digest = [0u8; 32];
+        transcript.challenge_bytes(b"history digest", &mut digest);
+        Self {
+            epoch: self.epoch + 1,
+            accumulator: *accumulator,
+            digest,
+        }
+    }
+
+    /// The checkpoint of the next epoch, after a key rotation
+    /// started the new accumulator `accumulator` under `public_keys`
+    pub fn rotate(&self, public_keys: &PublicKeys, accumulator: &Accumulator) -> Self {
+        let mut transcript = Transcript::new(b"allosaur_history_digest");
+        transcript.append_message(b"Previous digest", &self.digest);
+        transcript.append_u64(b"Epoch", self.epoch as u64);
+        transcript.append_message(b"Accumulator", self.accumulator.0.to_compressed().as_ref());
+        transcript.append_message(b"Rotated accumulator ID", &public_keys.accumulator_id());
+        let mut digest = [0u8; 32];
+        transcript.challenge_bytes(b"history digest", &mut digest);
+        Self {
+            epoch: self.epoch + 1,
+            accumulator: *accumulator,
+            digest,
+        }
+    }
+}
+
+/// A single deletion added to the revoked-set accumulator,
+/// moving a non-membership witness from `epoch` to `epoch + 1`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RevokedSetUpdate {
+    /// The epoch of the revoked-set accumulator before the deletion
+    pub epoch: usize,
+    /// The deleted user ID
+    pub deletion: UserID,
+    /// The revoked-set accumulator after the deletion
+    pub accumulator: Accumulator,
+}
+
+/// A single slice of a single-server update, i.e. a VB batch deletion
+/// that moves a witness from `epoch` to `epoch + deletions.len()`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitUpdate {
+    /// The epoch of the accumulator the slice starts from
+    pub epoch: usize,
+    /// The user IDs deleted in this slice
+    pub deletions: Vec<UserID>,
+    /// The update polynomial coefficients for this slice
+    pub coefficients: Vec<Coefficient>,
+    /// The accumulator after applying this slice
+    pub accumulator: Accumulator,
 }
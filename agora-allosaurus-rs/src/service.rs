@@ -0,0 +1,262 @@
+use crate::accumulator::{Accumulator, Coefficient, Element, MembershipWitness};
+use crate::servers::SplitUpdate;
+use crate::utils::{PublicKeys, UserID};
+use blsful::inner_types::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// A holder witness tracked by the [`WitnessUpdateService`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackedWitness {
+    /// The holder's membership witness
+    pub witness: MembershipWitness,
+    /// The epoch the witness is valid for
+    pub epoch: usize,
+}
+
+/// Keeps registered holder witnesses current with the published
+/// single-server updates, so holders can fetch a witness for the
+/// latest accumulator without processing every update themselves.
+///
+/// Every ingested update is checked to continue the chain of accumulators
+/// from the trusted publication the service was created with, and is kept
+/// split into single deletions so witnesses can be registered at any epoch.
+///
+/// Only the updates of the last `retain_epochs` epochs are kept, like the
+/// history of a pruned `Server`; older witnesses must be re-issued
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WitnessUpdateService {
+    /// The registry's public keys
+    public_keys: PublicKeys,
+    /// The number of epochs witnesses can still be registered from
+    retain_epochs: usize,
+    /// The oldest epoch a witness can be registered at
+    first_epoch: usize,
+    /// The accumulator at `first_epoch`
+    first_accumulator: Accumulator,
+    /// Every deletion ingested since `first_epoch`, in order
+    updates: Vec<SplitUpdate>,
+    /// The registered holders' witnesses for the latest epoch
+    holders: HashMap<UserID, MembershipWitness>,
+    /// Registered holders whose ID has been deleted
+    revoked: HashSet<UserID>,
+}
+
+impl WitnessUpdateService {
+    /// Creates a service that accepts updates starting at `epoch`, whose
+    /// accumulator is `accumulator` under the registry's `public_keys`
+    pub fn new(
+        epoch: usize,
+        accumulator: Accumulator,
+        public_keys: PublicKeys,
+        retain_epochs: usize,
+    ) -> Result<Self, &'static str> {
+        if retain_epochs == 0 {
+            return Err("must retain at least one epoch");
+        }
+        Ok(Self {
+            public_keys,
+            retain_epochs,
+            first_epoch: epoch,
+            first_accumulator: accumulator,
+            updates: Vec::new(),
+            holders: HashMap::new(),
+            revoked: HashSet::new(),
+        })
+    }
+
+    /// The latest epoch known to the service
+    pub fn get_epoch(&self) -> usize {
+        self.updates
+            .last()
+            .map_or(self.first_epoch, |u| u.epoch + u.deletions.len())
+    }
+
+    /// The latest accumulator known to the service
+    pub fn get_accumulator(&self) -> Accumulator {
+        self.updates
+            .last()
+            .map_or(self.first_accumulator, |u| u.accumulator)
+    }
+
+    /// The oldest epoch a witness can still be registered at
+    pub fn first_epoch(&self) -> usize {
+        self.first_epoch
+    }
+
+    /// Starts tracking a holder's witness, valid at `epoch`.
+    /// Returns an error if the witness is not valid at `epoch`
+    /// or the holder was already deleted after `epoch`
+    pub fn register(
+        &mut self,
+        id: UserID,
+        witness: MembershipWitness,
+        epoch: usize,
+    ) -> Result<(), &'static str> {
+        if epoch < self.first_epoch {
+            return Err("re-issue required");
+        }
+        let (accumulator, updates) = self.updates_since(epoch)?;
+        if !witness.verify(id, self.public_keys.witness_key, accumulator) {
+            return Err("invalid witness");
+        }
+        if self.revoked.contains(&id) || updates.iter().any(|u| u.deletions.contains(&id)) {
+            self.holders.remove(&id);
+            self.revoked.insert(id);
+            return Err("holder has been revoked");
+        }
+
+        let additions: &[Element] = &[];
+        let deltas: Vec<(&[Element], &[Element], &[Coefficient])> = updates
+            .iter()
+            .map(|u| (additions, u.deletions.as_slice(), u.coefficients.as_slice()))
+            .collect();
+        let mut witness = witness;
+        witness.multi_batch_update_assign(id, &deltas);
+        if !witness.verify(id, self.public_keys.witness_key, self.get_accumulator()) {
+            return Err("invalid update");
+        }
+        self.holders.insert(id, witness);
+        Ok(())
+    }
+
+    /// Stops tracking a holder
+    pub fn unregister(&mut self, id: &UserID) -> Option<TrackedWitness> {
+        self.revoked.remove(id);
+        let epoch = self.get_epoch();
+        self.holders
+            .remove(id)
+            .map(|witness| TrackedWitness { witness, epoch })
+    }
+
+    /// Applies the next update to every registered witness and returns the
+    /// registered holders it revokes.
+    ///
+    /// The service is left unchanged if the update does not continue from
+    /// the latest accumulator or any updated witness does not verify
+    pub fn ingest(&mut self, update: SplitUpdate) -> Result<Vec<UserID>, &'static str> {
+        if update.epoch != self.get_epoch() {
+            return Err("update does not start at the service's epoch");
+        }
+        let steps = self.split(&update)?;
+        let deleted: HashSet<UserID> = update.deletions.iter().copied().collect();
+        let mut revoked = Vec::new();
+        let mut holders = HashMap::with_capacity(self.holders.len());
+        for (id, witness) in &self.holders {
+            if deleted.contains(id) {
+                revoked.push(*id);
+                continue;
+            }
+            let mut witness = *witness;
+            witness.batch_update_assign(*id, &[], &update.deletions, &update.coefficients);
+            if !witness.verify(*id, self.public_keys.witness_key, update.accumulator) {
+                return Err("invalid update");
+            }
+            holders.insert(*id, witness);
+        }
+
+        self.holders = holders;
+        self.revoked.extend(revoked.iter().copied());
+        self.updates.extend(steps);
+        self.prune();
+        Ok(revoked)
+    }
+
+    /// Returns the holder's witness for the latest epoch
+    pub fn get_witness(&self, id: &UserID) -> Result<TrackedWitness, &'static str> {
+        if self.revoked.contains(id) {
+            return Err("holder has been revoked");
+        }
+        let witness = *self.holders.get(id).ok_or("unknown holder")?;
+        Ok(TrackedWitness {
+            witness,
+            epoch: self.get_epoch(),
+        })
+    }
+
+    /// Whether the holder has been revoked
+    pub fn is_revoked(&self, id: &UserID) -> bool {
+        self.revoked.contains(id)
+    }
+
+    /// The accumulator at `epoch` and the updates that
+    /// move a witness from there to the latest epoch
+    fn updates_since(&self, epoch: usize) -> Result<(Accumulator, &[SplitUpdate]), &'static str> {
+        if epoch < self.first_epoch || epoch > self.get_epoch() {
+            return Err("invalid epoch");
+        }
+        let i = epoch - self.first_epoch;
+        let accumulator = match i {
+            0 => self.first_accumulator,
+            _ => self.updates[i - 1].accumulator,
+        };
+        Ok((accumulator, &self.updates[i..]))
+    }
+
+    /// Splits `update` into one update per deletion, checking that each
+    /// intermediate accumulator removes its deletion from the previous one.
+    ///
+    /// With no additions the update polynomial is
+    /// Ω(x) = -∑ 1..m V_s ∏ 1..s-1 (y_j - x), where V_s is the accumulator
+    /// after the first s deletions, so Ω(y_s) only involves V_1..V_s
+    fn split(&self, update: &SplitUpdate) -> Result<Vec<SplitUpdate>, &'static str> {
+        if update.deletions.is_empty() || update.coefficients.len() != update.deletions.len() {
+            return Err("invalid update");
+        }
+        let coefficients: Vec<G1Projective> = update.coefficients.iter().map(|c| c.0).collect();
+        let mut previous = self.get_accumulator();
+        let mut accumulators: Vec<G1Projective> = Vec::with_capacity(update.deletions.len());
+        let mut steps = Vec::with_capacity(update.deletions.len());
+        for (s, y) in update.deletions.iter().enumerate() {
+            // ∏ 1..t-1 (y_j - y_s) for t = 1..s
+            let mut products = Vec::with_capacity(s + 1);
+            let mut product = Scalar::ONE;
+            for deleted in &update.deletions[..s] {
+                products.push(product);
+                product *= deleted.0 - y.0;
+            }
+            let inverse = Option::<Scalar>::from(product.invert()).ok_or("invalid update")?;
+            let mut powers = Vec::with_capacity(coefficients.len());
+            let mut power = Scalar::ONE;
+            for _ in 0..coefficients.len() {
+                powers.push(power);
+                power *= y.0;
+            }
+            let omega = G1Projective::sum_of_products(&coefficients, &powers);
+            let earlier = G1Projective::sum_of_products(&accumulators, &products);
+            let accumulator = -(omega + earlier) * inverse;
+
+            // V_s is a witness for y_s against V_s-1
+            if !MembershipWitness(accumulator).verify(*y, self.public_keys.witness_key, previous) {
+                return Err("invalid update");
+            }
+            previous = Accumulator(accumulator);
+            accumulators.push(accumulator);
+            steps.push(SplitUpdate {
+                epoch: update.epoch + s,
+                deletions: vec![*y],
+                coefficients: vec![Coefficient(-accumulator)],
+                accumulator: previous,
+            });
+        }
+        if previous != update.accumulator {
+            return Err("invalid update");
+        }
+        Ok(steps)
+    }
+
+    /// Drops the updates that end before the last `retain_epochs` epochs
+    fn prune(&mut self) {
+        let horizon = self.get_epoch().saturating_sub(self.retain_epochs - 1);
+        let n = self
+            .updates
+            .partition_point(|u| u.epoch + u.deletions.len() <= horizon);
+        if n == 0 {
+            return;
+        }
+        let last = &self.updates[n - 1];
+        self.first_epoch = last.epoch + last.deletions.len();
+        self.first_accumulator = last.accumulator;
+        self.updates.drain(..n);
+    }
+}
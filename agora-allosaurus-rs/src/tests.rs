@@ -1,8 +1,11 @@
 // Runs a suite of tests on the basic ALLOSAUR functionality
 
-use crate::accumulator::{Accumulator, Element, MembershipWitness, PublicKey, SecretKey};
+use crate::accumulator::{
+    Accumulator, Coefficient, Element, MembershipWitness, ProofMessage, PublicKey, SecretKey,
+};
 use crate::{utils::*, *};
 use blsful::inner_types::*;
+use merlin::Transcript;
 use rand::RngCore;
 
 // Generates a new accumulator and adds elements
@@ -21,8 +24,8 @@ fn test_shamir() {
     let threshold = 3;
     let num_shares = 5;
     let secret = Element::random().0;
-    let shares = shamir_share(threshold, num_shares, secret);
-    let coeffs = shamir_coefficients(threshold, &shares);
+    let shares = shamir_share(threshold, num_shares, secret, &mut rand::rngs::OsRng);
+    let coeffs = shamir_coefficients(threshold, &shares).unwrap();
     let rebuild = shamir_rebuild_scalar(&shares, &coeffs.0, &None);
     assert_eq!(secret, rebuild.unwrap());
 }
@@ -34,12 +37,22 @@ fn test_shamir_check() {
     let threshold = 3;
     let num_shares = 5;
     let secret = Scalar::random(rand::rngs::OsRng);
-    let shares = shamir_share(threshold, num_shares, secret);
-    let coeffs = shamir_coefficients(threshold, &shares);
+    let shares = shamir_share(threshold, num_shares, secret, &mut rand::rngs::OsRng);
+    let coeffs = shamir_coefficients(threshold, &shares).unwrap();
     let rebuild = shamir_rebuild_scalar(&shares, &coeffs.0, &coeffs.1);
     assert_eq!(secret, rebuild.unwrap());
 }
 
+// Tests that repeated share values are rejected instead of panicking
+#[test]
+fn test_shamir_repeated_shares() {
+    let mut shares = shamir_share(3, 5, Element::random().0, &mut rand::rngs::OsRng);
+    shares[2].0 = shares[1].0;
+    assert!(shamir_coefficients(3, &shares).is_err());
+    shares[2].0 = Scalar::ZERO;
+    assert!(shamir_coefficients(3, &shares).is_err());
+}
+
 // Test that basic affine transformations on Shamir shares
 // are homomorphic
 #[test]
@@ -49,11 +62,11 @@ fn test_shamir_affine() {
     let secret = Scalar::random(rand::rngs::OsRng);
     let a = Scalar::random(rand::rngs::OsRng);
     let b = Scalar::random(rand::rngs::OsRng);
-    let mut shares = shamir_share(threshold, num_shares, secret);
+    let mut shares = shamir_share(threshold, num_shares, secret, &mut rand::rngs::OsRng);
     for share in shares.iter_mut() {
         share.1 = share.1 * a + b;
     }
-    let coeffs = shamir_coefficients(threshold, &shares);
+    let coeffs = shamir_coefficients(threshold, &shares).unwrap();
     let rebuild = shamir_rebuild_scalar(&shares, &coeffs.0, &coeffs.1);
     assert_eq!(a * secret + b, rebuild.unwrap());
 }
@@ -68,13 +81,13 @@ fn test_shamir_point() {
     let secret_2 = Scalar::random(rand::rngs::OsRng);
     let a = G1Projective::generator();
     let b = G1Projective::generator() * SecretKey::new(None).0;
-    let shares_1 = shamir_share(threshold, num_shares, secret_1);
-    let shares_2 = shamir_share(threshold, num_shares, secret_2);
+    let shares_1 = shamir_share(threshold, num_shares, secret_1, &mut rand::rngs::OsRng);
+    let shares_2 = shamir_share(threshold, num_shares, secret_2, &mut rand::rngs::OsRng);
     let mut point_shares = Vec::new();
     for i in 0..shares_1.len() {
         point_shares.push((shares_1[i].0, a * shares_1[i].1 + b * shares_2[i].1));
     }
-    let coeffs = shamir_coefficients(threshold, &shares_1);
+    let coeffs = shamir_coefficients(threshold, &shares_1).unwrap();
     let rebuild = shamir_rebuild_point(&point_shares, &coeffs.0, &coeffs.1);
     assert_eq!(a * secret_1 + b * secret_2, rebuild.unwrap());
 }
@@ -229,6 +242,509 @@ fn basic_membership_proof_failure() {
     }
 }
 
+// Tests that batches of membership proofs verify and failing proofs are found
+#[test]
+fn batch_membership_proof() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..10 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let mut proofs = Vec::new();
+    for user in &users {
+        let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
+        let proof = user
+            .make_batchable_membership_proof(&params, &public_keys, &ephemeral_challenge)
+            .unwrap();
+        assert!(Witness::check_membership_proof(
+            &proof.proof,
+            &params,
+            &public_keys,
+            &accumulator,
+            &ephemeral_challenge
+        ));
+        proofs.push((proof, ephemeral_challenge));
+    }
+    assert_eq!(
+        Witness::batch_check_membership_proofs(&proofs, &params, &public_keys, &accumulator),
+        Ok(())
+    );
+    assert_eq!(
+        Witness::batch_check_membership_proofs(&[], &params, &public_keys, &accumulator),
+        Ok(())
+    );
+
+    // A response that only enters the pairing equations passes the transcript check
+    proofs[3].0.proof.s_0 += Scalar::ONE;
+    // A different ephemeral challenge fails the transcript check
+    proofs[7].1[0] ^= 1;
+    assert_eq!(
+        Witness::batch_check_membership_proofs(&proofs, &params, &public_keys, &accumulator),
+        Err(vec![3, 7])
+    );
+
+    // Proofs against an accumulator the users no longer belong to fail
+    let mut deleted = server.clone();
+    deleted.delete(users[0].get_id());
+    assert_eq!(
+        Witness::batch_check_membership_proofs(
+            &proofs[..2],
+            &params,
+            &public_keys,
+            &deleted.get_accumulator()
+        ),
+        Err(vec![0, 1])
+    );
+}
+
+// Tests binding a membership proof to an outer proof of the same user ID
+// through a shared blinding
+#[test]
+fn membership_proof_shared_blinding() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+    let witness = user.witness.as_ref().unwrap();
+    let y = user.get_id();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+
+    // The outer proof shows knowledge of y, r in C = yG + rH
+    let g = G1Projective::GENERATOR;
+    let h = G1Projective::GENERATOR * Element::random().0;
+    let r = Element::random().0;
+    let commitment = g * y.0 + h * r;
+    let k_y = Element::random().0;
+    let k_r = Element::random().0;
+    let t = g * k_y + h * k_r;
+    let bind = |t: G1Projective| {
+        let mut transcript = Transcript::new(b"outer_proof");
+        transcript.append_message(b"T", &t.to_compressed());
+        let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+        transcript.challenge_bytes(b"ephemeral challenge", &mut ephemeral_challenge);
+        ephemeral_challenge
+    };
+    let ephemeral_challenge = bind(t);
+
+    let proof = Witness::make_membership_proof_with_messages(
+        witness,
+        ProofMessage::SharedBlinding {
+            message: y.0,
+            blinder: k_y,
+        },
+        ProofMessage::Hidden {
+            message: witness.secret_key.0,
+        },
+        &accumulator,
+        &params,
+        &public_keys,
+        &ephemeral_challenge,
+    )
+    .unwrap()
+    .proof;
+    let c = proof.get_challenge();
+    let s_r = k_r + c * r;
+    assert_eq!(proof.get_user_id_response(), k_y + c * y.0);
+
+    // The verifier rebuilds the outer commitment from the shared response
+    let t_check = g * proof.get_user_id_response() + h * s_r - commitment * c;
+    assert!(Witness::check_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &accumulator,
+        &bind(t_check)
+    ));
+
+    // A commitment to a different ID does not link to the proof
+    let other = g * Element::random().0 + h * r;
+    assert_ne!(g * proof.get_user_id_response() + h * s_r - other * c, t);
+
+    // The hidden values must match the witness
+    assert!(Witness::make_membership_proof_with_messages(
+        witness,
+        ProofMessage::Hidden { message: y.0 },
+        ProofMessage::Hidden {
+            message: Element::random().0,
+        },
+        &accumulator,
+        &params,
+        &public_keys,
+        &ephemeral_challenge,
+    )
+    .is_none());
+    assert!(Witness::make_membership_proof_with_messages(
+        witness,
+        ProofMessage::Hidden {
+            message: Element::random().0,
+        },
+        ProofMessage::Hidden {
+            message: witness.secret_key.0,
+        },
+        &accumulator,
+        &params,
+        &public_keys,
+        &ephemeral_challenge,
+    )
+    .is_none());
+}
+
+// Tests composing a membership proof with an outer proof under one challenge
+#[test]
+fn composed_membership_proof() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+    let witness = user.witness.as_ref().unwrap();
+    let y = user.get_id();
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+
+    // The outer proof shows knowledge of y, r in C = yG + rH
+    let g = G1Projective::GENERATOR;
+    let h = G1Projective::GENERATOR * Element::random().0;
+    let r = Element::random().0;
+    let commitment = g * y.0 + h * r;
+    let k_y = Element::random().0;
+    let k_r = Element::random().0;
+
+    // Commit
+    let mpc = MembershipProofCommitting::new(
+        witness,
+        ProofMessage::SharedBlinding {
+            message: y.0,
+            blinder: k_y,
+        },
+        ProofMessage::Hidden {
+            message: witness.secret_key.0,
+        },
+        &accumulator,
+        &params,
+        &public_keys,
+    )
+    .unwrap();
+    let t = g * k_y + h * k_r;
+
+    // Challenge
+    let mut transcript = Transcript::new(b"composed_proof");
+    mpc.get_bytes_for_challenge(&mut transcript);
+    transcript.append_message(b"C", &commitment.to_compressed());
+    transcript.append_message(b"T", &t.to_compressed());
+    let challenge = Element::from_transcript(b"challenge", &mut transcript);
+
+    // Respond
+    let proof = mpc.gen_proof(challenge);
+    let s_r = k_r + challenge.0 * r;
+
+    // Verify both proofs against the same transcript
+    let verify = |s_r: Scalar| {
+        let c = proof.get_challenge();
+        let mut transcript = Transcript::new(b"composed_proof");
+        proof.get_bytes_for_challenge(&params, &public_keys, &accumulator, &mut transcript);
+        let t = g * proof.get_user_id_response() + h * s_r - commitment * c;
+        transcript.append_message(b"C", &commitment.to_compressed());
+        transcript.append_message(b"T", &t.to_compressed());
+        proof.check_challenge(Element::from_transcript(b"challenge", &mut transcript))
+    };
+    assert!(verify(s_r));
+    assert!(!verify(s_r + Scalar::ONE));
+
+    // The witness must be valid for the accumulator
+    let mut deleted = server.clone();
+    deleted.delete(y);
+    assert!(MembershipProofCommitting::new(
+        witness,
+        ProofMessage::Hidden { message: y.0 },
+        ProofMessage::Hidden {
+            message: witness.secret_key.0,
+        },
+        &deleted.get_accumulator(),
+        &params,
+        &public_keys,
+    )
+    .is_none());
+}
+
+// Tests epoch-bound membership proofs and the verification window
+#[test]
+fn epoch_membership_proof_window() {
+    const SERVERS: usize = 3;
+    const SERVER_THRESHOLD: usize = 2;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..3 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    let public_keys = server.get_public_keys();
+    let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
+
+    let proof = users[0]
+        .make_epoch_membership_proof(&params, &public_keys, &ephemeral_challenge)
+        .unwrap();
+    assert_eq!(proof.epoch, 1);
+    assert_eq!(proof.accumulator_id, public_keys.accumulator_id());
+    let history = server.publish_accumulator_history(usize::MAX);
+    assert!(Witness::check_membership_proof_in_window(
+        &proof,
+        &params,
+        &public_keys,
+        &history,
+        1,
+        &ephemeral_challenge
+    )
+    .is_ok());
+
+    server.delete(users[2].get_id());
+    server.delete(users[1].get_id());
+    assert_eq!(server.get_epoch(), 3);
+    let history = server.publish_accumulator_history(usize::MAX);
+    let check = |proof: &EpochMembershipProof, history: &[(usize, Accumulator)], window| {
+        Witness::check_membership_proof_in_window(
+            proof,
+            &params,
+            &public_keys,
+            history,
+            window,
+            &ephemeral_challenge,
+        )
+    };
+    assert!(!Witness::check_epoch_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &server.get_accumulator(),
+        &ephemeral_challenge
+    ));
+    assert_eq!(
+        check(&proof, &history, 2),
+        Err("proof epoch is outside the verification window")
+    );
+    assert!(check(&proof, &history, 3).is_ok());
+    assert!(check(&proof, &history, usize::MAX).is_ok());
+
+    // The epoch is part of the transcript
+    let mut moved = proof;
+    moved.epoch = 2;
+    assert_eq!(check(&moved, &history, 3), Err("invalid membership proof"));
+    moved.epoch = 4;
+    assert_eq!(check(&moved, &history, 3), Err("unknown epoch"));
+    let other = Server::new(&params);
+    assert_eq!(
+        Witness::check_membership_proof_in_window(
+            &proof,
+            &params,
+            &other.get_public_keys(),
+            &history,
+            3,
+            &ephemeral_challenge
+        ),
+        Err("unknown accumulator")
+    );
+    // Verifiers that were only given the latest accumulators cannot check older proofs
+    assert_eq!(
+        check(&proof, &server.publish_accumulator_history(2), 3),
+        Err("re-issue required")
+    );
+
+    // After an update the user proves against the latest epoch
+    let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
+    assert!(users[0].update(&servers, SERVER_THRESHOLD).is_ok());
+    assert_eq!(users[0].epoch, 3);
+    let proof = users[0]
+        .make_epoch_membership_proof(&params, &public_keys, &ephemeral_challenge)
+        .unwrap();
+    assert_eq!(proof.epoch, 3);
+    assert!(check(&proof, &server.publish_accumulator_history(1), 1).is_ok());
+}
+
+// Tests non-membership witnesses and proofs for the revoked set
+#[test]
+fn non_membership_proof() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..5 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+        users
+            .last_mut()
+            .unwrap()
+            .create_non_membership_witness(&params, &server)
+            .unwrap();
+    }
+    let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
+    let public_key = server.get_revoked_public_key();
+
+    let proof = users[0]
+        .make_non_membership_proof(&ephemeral_challenge)
+        .unwrap();
+    assert!(proof.verify(
+        &server.get_revoked_accumulator(),
+        &public_key,
+        &ephemeral_challenge
+    ));
+
+    let epoch = server.get_epoch();
+    server.delete(users[1].get_id());
+    server.quick_delete(users[2].get_id());
+    assert!(!proof.verify(
+        &server.get_revoked_accumulator(),
+        &public_key,
+        &ephemeral_challenge
+    ));
+    assert_eq!(
+        users[1].create_non_membership_witness(&params, &server),
+        Err("user has been deleted")
+    );
+    // Witnesses are only issued to holders of a signature for the ID
+    let mut request = users[3].make_reissue_request(&params).unwrap();
+    request.id = users[4].get_id();
+    assert_eq!(
+        server
+            .non_membership_witness(&params, &request)
+            .unwrap_err(),
+        "invalid re-issue request"
+    );
+
+    let updates = server.publish_revoked_set_updates_since(epoch).unwrap();
+    assert_eq!(updates.len(), 2);
+    assert_eq!(
+        users[3].apply_revoked_set_updates(&updates[1..]),
+        Err("update does not start at the user's epoch")
+    );
+    assert_eq!(
+        users[2].apply_revoked_set_updates(&updates),
+        Err("user has been deleted")
+    );
+    // An update with a forged accumulator is rejected and leaves the witness unchanged
+    let mut forged = updates.clone();
+    forged[1].accumulator = Accumulator(G1Projective::GENERATOR);
+    let before = users[0].non_membership.unwrap();
+    assert_eq!(
+        users[0].apply_revoked_set_updates(&forged),
+        Err("updated non-membership witness is invalid")
+    );
+    assert_eq!(users[0].non_membership.unwrap().epoch, before.epoch);
+    assert_eq!(
+        users[0].non_membership.unwrap().accumulator,
+        before.accumulator
+    );
+    users[0].apply_revoked_set_updates(&updates).unwrap();
+    // Updates that were already applied are skipped
+    users[0].apply_revoked_set_updates(&updates).unwrap();
+    let state = users[0].non_membership.unwrap();
+    assert_eq!(state.epoch, server.get_epoch());
+    assert_eq!(state.accumulator, server.get_revoked_accumulator());
+
+    let proof = users[0]
+        .make_non_membership_proof(&ephemeral_challenge)
+        .unwrap();
+    assert!(proof.verify(
+        &server.get_revoked_accumulator(),
+        &public_key,
+        &ephemeral_challenge
+    ));
+    ephemeral_challenge[0] ^= 1;
+    assert!(!proof.verify(
+        &server.get_revoked_accumulator(),
+        &public_key,
+        &ephemeral_challenge
+    ));
+}
+
+// Tests binding a non-membership proof to a membership proof of the same user ID
+#[test]
+fn composed_non_membership_proof() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..2 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+        users
+            .last_mut()
+            .unwrap()
+            .create_non_membership_witness(&params, &server)
+            .unwrap();
+    }
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let k_y = Element::random().0;
+
+    // Commit to both proofs with a shared blinding for the user ID
+    let prove = |member: &User, revoked: &User| {
+        let witness = member.witness.as_ref().unwrap();
+        let mpc = MembershipProofCommitting::new(
+            witness,
+            ProofMessage::SharedBlinding {
+                message: member.get_id().0,
+                blinder: k_y,
+            },
+            ProofMessage::Hidden {
+                message: witness.secret_key.0,
+            },
+            &accumulator,
+            &params,
+            &public_keys,
+        )
+        .unwrap();
+        let state = revoked.non_membership.unwrap();
+        let nmpc = NonMembershipProofCommitting::new(
+            ProofMessage::SharedBlinding {
+                message: revoked.get_id().0,
+                blinder: k_y,
+            },
+            &state.witness,
+            &state.accumulator,
+            &state.public_key,
+        )
+        .unwrap();
+        let mut transcript = Transcript::new(b"composed_proof");
+        mpc.get_bytes_for_challenge(&mut transcript);
+        nmpc.get_bytes_for_challenge(&mut transcript);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        (mpc.gen_proof(challenge), nmpc.gen_proof(challenge))
+    };
+
+    // The verifier recomputes the shared challenge and compares the responses
+    let verify = |membership: &MembershipProof, non_membership: &NonMembershipProof| {
+        let mut transcript = Transcript::new(b"composed_proof");
+        membership.get_bytes_for_challenge(&params, &public_keys, &accumulator, &mut transcript);
+        non_membership.get_bytes_for_challenge(
+            &server.get_revoked_accumulator(),
+            &server.get_revoked_public_key(),
+            &mut transcript,
+        );
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        membership.check_challenge(challenge)
+            && non_membership.check_challenge(challenge)
+            && membership.get_user_id_response() == non_membership.get_user_id_response()
+    };
+    let (membership, non_membership) = prove(&users[0], &users[0]);
+    assert!(verify(&membership, &non_membership));
+
+    // A non-membership proof for another user ID does not bind
+    let (membership, non_membership) = prove(&users[0], &users[1]);
+    assert!(!verify(&membership, &non_membership));
+}
+
 // Tests that the split update works correctly
 #[test]
 fn test_split_witness_update() {
@@ -263,7 +779,7 @@ fn test_split_witness_update() {
         y_values,
     } = res.unwrap();
     let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..SERVERS)
-        .map(|i| servers[i].update(d, &y_shares[i]))
+        .map(|i| servers[i].update(d, &y_shares[i]).unwrap())
         .collect();
     let res = users[0].post_update(
         users[0].witness.as_ref().unwrap().witness,
@@ -319,7 +835,7 @@ fn test_witness_split_update_add() {
         y_values,
     } = res.unwrap();
     let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..SERVERS)
-        .map(|i| servers[i].update(d, &y_shares[i]))
+        .map(|i| servers[i].update(d, &y_shares[i]).unwrap())
         .collect();
 
     let res = users[0].post_update(
@@ -375,3 +891,1366 @@ fn single_server_split_batch_update() {
     }
     assert!(witness.verify(y, pk, acc));
 }
+
+// Tests that an encrypted wallet export restores the same user
+// and is rejected under the wrong key
+#[test]
+fn test_encrypted_wallet_round_trip() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+
+    let wallet = user
+        .export_encrypted(WalletKey::Password(b"correct horse battery staple"))
+        .unwrap();
+    let restored = User::import_encrypted(
+        &wallet,
+        WalletKey::Password(b"correct horse battery staple"),
+    )
+    .unwrap();
+    assert_eq!(restored.get_id(), user.get_id());
+    assert!(restored
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+    assert!(User::import_encrypted(&wallet, WalletKey::Password(b"wrong password")).is_err());
+
+    let mut key = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut key);
+    let wallet = user.export_encrypted(WalletKey::Key(&key)).unwrap();
+    assert!(User::import_encrypted(&wallet, WalletKey::Key(&key)).is_ok());
+    assert!(User::import_encrypted(&wallet, WalletKey::Password(&key)).is_err());
+
+    let mut tampered = wallet.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(User::import_encrypted(&tampered, WalletKey::Key(&key)).is_err());
+}
+
+// Tests that users and servers round-trip through their versioned encoding
+// and that blobs in the layout from before versioning still decode
+#[test]
+fn test_versioned_serialization() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+    let mut deleted = User::new(&server, UserID::random());
+    server.add(deleted.get_id());
+    deleted.create_witness(&params, &server);
+    server.delete(deleted.get_id());
+
+    let bytes = user.to_bytes().unwrap();
+    assert_eq!(bytes[0], USER_FORMAT_VERSION);
+    let restored = User::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.get_id(), user.get_id());
+    assert_eq!(restored.epoch, user.epoch);
+
+    let legacy = postcard::to_stdvec(&(
+        user.id,
+        &user.witness,
+        user.accumulator,
+        user.public_keys,
+        user.epoch,
+    ))
+    .unwrap();
+    let restored = User::from_bytes(&legacy).unwrap();
+    assert_eq!(restored.get_id(), user.get_id());
+    assert_eq!(restored.epoch, user.epoch);
+    assert!(restored.non_membership.is_none());
+    assert!(restored.check_witness(&params, &user.accumulator).is_ok());
+    assert!(User::from_bytes(&legacy[..legacy.len() - 1]).is_err());
+
+    let bytes = server.to_bytes().unwrap();
+    assert_eq!(bytes[0], SERVER_FORMAT_VERSION);
+    let restored = Server::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.get_epoch(), server.get_epoch());
+    assert_eq!(restored.get_accumulator(), server.get_accumulator());
+
+    let legacy = postcard::to_stdvec(&(
+        &server.accumulators,
+        &server.witness_secret_key,
+        server.public_keys,
+        &server.sign_secret_key,
+        &server.all_users,
+        &server.all_witnesses,
+        &server.deletions,
+    ))
+    .unwrap();
+    let restored = Server::from_bytes(&legacy).unwrap();
+    assert_eq!(restored.get_epoch(), server.get_epoch());
+    assert_eq!(restored.get_accumulator(), server.get_accumulator());
+    assert_eq!(restored.deletions, server.deletions);
+    let request = deleted.make_reissue_request(&params).unwrap();
+    assert!(restored.non_membership_witness(&params, &request).is_err());
+    let request = user.make_reissue_request(&params).unwrap();
+    assert!(restored.non_membership_witness(&params, &request).is_ok());
+
+    // A history without one accumulator per deletion is rejected
+    let inconsistent = postcard::to_stdvec(&(
+        &server.accumulators[..1],
+        &server.witness_secret_key,
+        server.public_keys,
+        &server.sign_secret_key,
+        &server.all_users,
+        &server.all_witnesses,
+        &server.deletions,
+    ))
+    .unwrap();
+    assert!(Server::from_bytes(&inconsistent).is_err());
+}
+
+// Tests that secret-dependent inversions fail cleanly instead of panicking
+// when an ID collides with the negation of a server secret key
+#[test]
+fn test_degenerate_ids_do_not_panic() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let bad_id = Element(-server.witness_secret_key.0);
+    assert!(server.add(bad_id).is_none());
+    assert!(!server.all_users.contains(&bad_id));
+    assert!(server.quick_delete(bad_id).is_none());
+
+    let bad_id = Element(-server.sign_secret_key.0);
+    assert!(server.add(bad_id).is_some());
+    let mut user = User::new(&server, bad_id);
+    user.create_witness(&params, &server);
+    assert!(user.witness.is_none());
+}
+
+// Tests that a deleted user learns about the deletion only once
+// the whole update has been processed
+#[test]
+fn test_deleted_user_update_fails() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 6;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    for user in users.iter().skip(1) {
+        server.delete(user.get_id());
+    }
+    let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
+    assert_eq!(
+        users[2].update(&servers, SERVER_THRESHOLD),
+        Err("user has been deleted")
+    );
+    assert!(users[0].update(&servers, SERVER_THRESHOLD).is_ok());
+}
+
+// Tests that every update strategy produces a valid witness
+// and that the cost model matches the messages actually exchanged
+#[test]
+fn test_update_strategies() {
+    const SERVERS: usize = 5;
+    const SERVER_THRESHOLD: usize = 3;
+    const USERS: usize = 12;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    for user in users.iter().skip(1) {
+        server.delete(user.get_id());
+    }
+    let servers: Vec<Server> = (0..SERVERS).map(|_| server.clone()).collect();
+    let epoch = server.get_epoch();
+    for strategy in [
+        UpdateStrategy::Balanced,
+        UpdateStrategy::MinimizeUpload,
+        UpdateStrategy::MinimizeUserOperations,
+        UpdateStrategy::Fixed(4),
+    ] {
+        let cost = users[0]
+            .update_cost(epoch, SERVERS, SERVER_THRESHOLD, strategy)
+            .unwrap();
+        let update = users[0]
+            .prepare_for_update_with_strategy(epoch, SERVERS, SERVER_THRESHOLD, strategy)
+            .unwrap();
+        assert_eq!(update.epoch_diff, USERS - 1);
+        assert_eq!(update.y_shares[0].len(), cost.chunk_size - 1);
+        let (ds, vs) = servers[0]
+            .update(update.epoch_diff, &update.y_shares[0])
+            .unwrap();
+        assert_eq!(ds.len(), cost.num_chunks);
+        assert_eq!(vs.len(), cost.num_chunks);
+
+        let mut user = users[0].clone();
+        assert!(user
+            .update_with_strategy(&servers, SERVER_THRESHOLD, strategy)
+            .is_ok());
+        assert!(user
+            .check_witness(&params, &server.get_accumulator())
+            .is_ok());
+    }
+
+    let upload = UpdateCost::new(
+        100,
+        SERVERS,
+        SERVER_THRESHOLD,
+        UpdateStrategy::MinimizeUpload,
+    );
+    let ops = UpdateCost::new(
+        100,
+        SERVERS,
+        SERVER_THRESHOLD,
+        UpdateStrategy::MinimizeUserOperations,
+    );
+    assert!(upload.upload_bytes < ops.upload_bytes);
+    assert!(ops.user_group_operations < upload.user_group_operations);
+    assert!(users[0]
+        .update_cost(0, SERVERS, SERVER_THRESHOLD, UpdateStrategy::Balanced)
+        .is_err());
+}
+
+// Tests the single-server split update API for users at different epochs
+#[test]
+fn test_publish_split_updates() {
+    const USERS: usize = 12;
+    const SLICE: usize = 4;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    for user in users.iter().skip(2).take(5) {
+        server.delete(user.get_id());
+    }
+    // A user issued a witness in the middle of the deletions
+    let mut late_user = User::new(&server, UserID::random());
+    server.add(late_user.get_id());
+    late_user.create_witness(&params, &server);
+    for user in users.iter().skip(7) {
+        server.delete(user.get_id());
+    }
+
+    let updates = server.publish_split_updates(SLICE).unwrap();
+    assert_eq!(updates.len(), (USERS - 2).div_ceil(SLICE));
+    assert_eq!(
+        updates.last().unwrap().accumulator,
+        server.get_accumulator()
+    );
+    for user in users.iter_mut().take(2) {
+        assert!(user.apply_split_updates(&params, &updates).is_ok());
+        assert_eq!(user.epoch, server.get_epoch());
+        assert!(user
+            .check_witness(&params, &server.get_accumulator())
+            .is_ok());
+    }
+    assert_eq!(
+        users[3].apply_split_updates(&params, &updates),
+        Err("user has been deleted")
+    );
+
+    assert!(late_user.apply_split_updates(&params, &updates).is_err());
+    let updates = server
+        .publish_split_updates_since(late_user.epoch, SLICE)
+        .unwrap();
+    // A forged accumulator is rejected without changing the user
+    let mut forged = updates.clone();
+    forged.last_mut().unwrap().accumulator = Accumulator::random();
+    let epoch = late_user.epoch;
+    assert_eq!(
+        late_user.apply_split_updates(&params, &forged),
+        Err("updated witness is invalid")
+    );
+    assert_eq!(late_user.epoch, epoch);
+    assert!(late_user.apply_split_updates(&params, &updates).is_ok());
+    assert!(late_user
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+}
+
+// Tests that the witness update service keeps registered holders current,
+// reports holders that were deleted and rejects forged updates
+#[test]
+fn test_witness_update_service() {
+    const USERS: usize = 10;
+    const SLICE: usize = 3;
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..USERS {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+
+    let mut service = WitnessUpdateService::new(
+        server.get_epoch(),
+        server.get_accumulator(),
+        server.get_public_keys(),
+        6,
+    )
+    .unwrap();
+    let mut unregistered = service.clone();
+    for user in users.iter().skip(4) {
+        let witness = user.witness.as_ref().unwrap().witness;
+        assert!(service.register(user.get_id(), witness, user.epoch).is_ok());
+    }
+    assert_eq!(
+        service.register(
+            UserID::random(),
+            MembershipWitness(G1Projective::GENERATOR),
+            server.get_epoch()
+        ),
+        Err("invalid witness")
+    );
+
+    for user in users.iter().take(5) {
+        server.delete(user.get_id());
+    }
+    let updates = server
+        .publish_split_updates_since(users[0].epoch, SLICE)
+        .unwrap();
+    let mut forged = updates[0].clone();
+    forged.accumulator = Accumulator::random();
+    assert_eq!(service.ingest(forged), Err("invalid update"));
+    assert_eq!(service.get_epoch(), users[0].epoch);
+
+    // Updates are checked even when no holder is registered
+    let mut forged = updates[0].clone();
+    forged.coefficients[1] = Coefficient(G1Projective::GENERATOR);
+    assert_eq!(unregistered.ingest(forged), Err("invalid update"));
+    for update in updates.iter() {
+        unregistered.ingest(update.clone()).unwrap();
+    }
+    // Holders whose epoch falls inside an ingested slice can still register
+    let mut holder = users[9].clone();
+    let single = server.publish_split_updates_since(holder.epoch, 1).unwrap();
+    holder.apply_split_updates(&params, &single[..1]).unwrap();
+    assert_eq!(holder.epoch, users[0].epoch + 1);
+    let witness = holder.witness.as_ref().unwrap().witness;
+    assert!(unregistered
+        .register(holder.get_id(), witness, holder.epoch)
+        .is_ok());
+    assert!(unregistered
+        .get_witness(&holder.get_id())
+        .unwrap()
+        .witness
+        .verify(
+            holder.get_id(),
+            server.get_witness_public_key(),
+            server.get_accumulator()
+        ));
+
+    let revoked: Vec<UserID> = updates
+        .into_iter()
+        .flat_map(|update| service.ingest(update).unwrap())
+        .collect();
+    assert_eq!(revoked, vec![users[4].get_id()]);
+    assert_eq!(service.get_epoch(), server.get_epoch());
+
+    for user in users.iter().skip(5) {
+        let tracked = service.get_witness(&user.get_id()).unwrap();
+        assert_eq!(tracked.epoch, server.get_epoch());
+        assert!(tracked.witness.verify(
+            user.get_id(),
+            server.get_witness_public_key(),
+            server.get_accumulator()
+        ));
+    }
+    assert!(service.is_revoked(&users[4].get_id()));
+    assert_eq!(
+        service.get_witness(&users[4].get_id()),
+        Err("holder has been revoked")
+    );
+    let witness = users[4].witness.as_ref().unwrap().witness;
+    assert!(service
+        .register(users[4].get_id(), witness, users[4].epoch)
+        .is_err());
+
+    // Only the updates of the retained epochs are kept
+    for user in users.iter().skip(5).take(3) {
+        server.delete(user.get_id());
+    }
+    for update in server
+        .publish_split_updates_since(service.get_epoch(), SLICE)
+        .unwrap()
+    {
+        service.ingest(update).unwrap();
+    }
+    assert_eq!(service.first_epoch(), users[0].epoch + SLICE);
+    let witness = users[9].witness.as_ref().unwrap().witness;
+    assert_eq!(
+        service.register(users[9].get_id(), witness, users[9].epoch),
+        Err("re-issue required")
+    );
+}
+
+// Known answer tests for deriving user IDs from credential identifiers.
+// These must match agora-allosaurus-py/allosaur/user_id.py
+const CREDENTIAL_ID_VECTORS: [(&str, &str, &str); 4] = [
+    (
+        "",
+        "0",
+        "5676bea2a9f747611f0fcbb0ed90ffced2ddad7993e0042270cd1ae1e11b329b",
+    ),
+    (
+        "Th7MpTaRZVRYnPiabds81Y:4:Th7MpTaRZVRYnPiabds81Y:3:CL:185:default:CL_ACCUM:TAG1",
+        "1",
+        "21fe3b2f3dc7dd4fc4472d8e82009514ddca6c15da41a039c1566ffc6978017c",
+    ),
+    (
+        "Th7MpTaRZVRYnPiabds81Y:4:Th7MpTaRZVRYnPiabds81Y:3:CL:185:default:CL_ACCUM:TAG1",
+        "100000",
+        "1db9751d300b7d7a10bc1b9035255ba6215b7f70e0cbaf02bb3a7a2c47ad0a87",
+    ),
+    (
+        "did:example:registry/rev_reg/1",
+        "42",
+        "0daa6fe9dfec1b778f3978c65936262b988080dfff2693a21883356e55c8c87f",
+    ),
+];
+
+#[test]
+fn test_user_id_from_credential_id() {
+    for (registry_id, cred_rev_id, expected) in CREDENTIAL_ID_VECTORS {
+        let id = UserID::from_credential_id(registry_id, cred_rev_id);
+        let hex: String = id.to_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, expected);
+    }
+    // The length prefixes keep the two fields from running into each other
+    assert_ne!(
+        UserID::from_credential_id("registry1", "2"),
+        UserID::from_credential_id("registry", "12")
+    );
+    assert_ne!(
+        UserID::from_credential_id("registry", "1"),
+        Element::hash(b"registry1")
+    );
+}
+
+// Tests that verifier challenges are single use, expire and are bound to the verifier
+#[test]
+fn verifier_session_challenges() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+
+    let mut session = VerifierSession::new([1u8; 32], 60, 16);
+    let mut other = VerifierSession::new([2u8; 32], 60, 16);
+
+    let challenge = session.issue_challenge(1000).unwrap();
+    let proof = user
+        .make_membership_proof(&params, &public_keys, &challenge)
+        .unwrap();
+    assert_eq!(
+        other.verify_membership_proof(
+            &proof,
+            &challenge,
+            &params,
+            &public_keys,
+            &accumulator,
+            1001
+        ),
+        Err("challenge was not issued by this verifier")
+    );
+    assert!(session
+        .verify_membership_proof(
+            &proof,
+            &challenge,
+            &params,
+            &public_keys,
+            &accumulator,
+            1001
+        )
+        .is_ok());
+    assert_eq!(
+        session.verify_membership_proof(
+            &proof,
+            &challenge,
+            &params,
+            &public_keys,
+            &accumulator,
+            1002
+        ),
+        Err("challenge has already been used")
+    );
+
+    // A proof made with a different challenge does not redeem this one
+    let challenge = session.issue_challenge(2000).unwrap();
+    let mut stale = [0u8; 2 * SECURITY_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut stale);
+    let proof = user
+        .make_membership_proof(&params, &public_keys, &stale)
+        .unwrap();
+    assert_eq!(
+        session.verify_membership_proof(
+            &proof,
+            &challenge,
+            &params,
+            &public_keys,
+            &accumulator,
+            2001
+        ),
+        Err("invalid membership proof")
+    );
+
+    let challenge = session.issue_challenge(3000).unwrap();
+    let proof = user
+        .make_epoch_membership_proof(&params, &public_keys, &challenge)
+        .unwrap();
+    assert_eq!(
+        session.verify_epoch_membership_proof(
+            &proof,
+            &challenge,
+            &params,
+            &public_keys,
+            &server.publish_accumulator_history(1),
+            1,
+            3060
+        ),
+        Err("challenge has expired")
+    );
+
+    let challenge = session.issue_challenge(4000).unwrap();
+    let proof = user
+        .make_epoch_membership_proof(&params, &public_keys, &challenge)
+        .unwrap();
+    assert!(session
+        .verify_epoch_membership_proof(
+            &proof,
+            &challenge,
+            &params,
+            &public_keys,
+            &server.publish_accumulator_history(1),
+            1,
+            4059
+        )
+        .is_ok());
+
+    // Pruning forgets used challenges once they could no longer be redeemed
+    session.prune(5000);
+    assert_eq!(
+        session.redeem(&challenge, 5000),
+        Err("challenge was not issued by this verifier")
+    );
+
+    // Issued and redeemed challenges count against the capacity until they expire
+    let mut session = VerifierSession::new([1u8; 32], 60, 2);
+    let challenge = session.issue_challenge(6000).unwrap();
+    session.redeem(&challenge, 6001).unwrap();
+    session.issue_challenge(6002).unwrap();
+    assert_eq!(
+        session.issue_challenge(6003),
+        Err("too many outstanding challenges")
+    );
+    assert!(session.issue_challenge(6060).is_ok());
+}
+
+// Tests that non-revocation presentations verify against the matching publication only
+#[test]
+fn non_revocation_presentation() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..2 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    let public_keys = server.get_public_keys();
+    let publication = server.publish("registry");
+    let mut session = VerifierSession::new([3u8; 32], 60, 16);
+
+    let challenge = session.issue_challenge(0).unwrap();
+    let presentation = users[0]
+        .make_non_revocation_presentation("registry", &params, &public_keys, &challenge)
+        .unwrap();
+    assert_eq!(presentation.epoch, publication.epoch);
+    assert_eq!(
+        presentation.accumulator_digest,
+        accumulator_digest(&publication.accumulator)
+    );
+    assert!(presentation.verify(&publication, &params).is_ok());
+    assert!(session
+        .verify_presentation(&presentation, &publication, &params, 1)
+        .is_ok());
+    assert_eq!(
+        session.verify_presentation(&presentation, &publication, &params, 2),
+        Err("challenge has already been used")
+    );
+
+    // The context is part of the transcript
+    let mut other = publication.clone();
+    other.registry_id = "other".to_string();
+    let mut moved = presentation.clone();
+    moved.registry_id = "other".to_string();
+    assert_eq!(
+        presentation.verify(&other, &params),
+        Err("presentation is for a different registry")
+    );
+    assert_eq!(
+        moved.verify(&other, &params),
+        Err("invalid membership proof")
+    );
+
+    // A publication from a later epoch no longer matches
+    server.delete(users[1].get_id());
+    let later = server.publish("registry");
+    assert_eq!(
+        presentation.verify(&later, &params),
+        Err("presentation epoch does not match the publication")
+    );
+    let mut stale = later.clone();
+    stale.epoch = presentation.epoch;
+    assert_eq!(
+        presentation.verify(&stale, &params),
+        Err("presentation accumulator does not match the publication")
+    );
+    let other_keys = Server::new(&params).publish("registry");
+    let mut foreign = publication;
+    foreign.public_keys = other_keys.public_keys;
+    assert_eq!(
+        presentation.verify(&foreign, &params),
+        Err("presentation public keys do not match the registry")
+    );
+}
+
+// Tests that a prepared verifier agrees with the static verification functions
+#[test]
+fn prepared_verifier() {
+    let params = AccParams::default();
+    assert_eq!(params, AccParams::default());
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..3 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+    }
+    for user in users.iter_mut() {
+        user.create_witness(&params, &server);
+    }
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+    let verifier = PreparedVerifier::new(&params, &public_keys);
+
+    let mut proofs = Vec::new();
+    for user in &users {
+        let witness = user.witness.as_ref().unwrap();
+        assert!(verifier
+            .verify_witness(&accumulator, &user.get_id(), witness)
+            .is_ok());
+        assert!(verifier
+            .verify_witness(&accumulator, &UserID::random(), witness)
+            .is_err());
+
+        let mut ephemeral_challenge = [0u8; 2 * SECURITY_BYTES];
+        rand::rngs::OsRng.fill_bytes(&mut ephemeral_challenge);
+        let proof = user
+            .make_batchable_membership_proof(&params, &public_keys, &ephemeral_challenge)
+            .unwrap();
+        assert!(verifier.check_membership_proof(&proof.proof, &accumulator, &ephemeral_challenge));
+        assert!(!verifier.check_membership_proof(
+            &proof.proof,
+            &Accumulator::random(),
+            &ephemeral_challenge
+        ));
+        proofs.push((proof, ephemeral_challenge));
+    }
+    assert!(verifier
+        .batch_check_membership_proofs(&proofs, &accumulator)
+        .is_ok());
+    proofs[1].1[0] ^= 1;
+    assert_eq!(
+        verifier.batch_check_membership_proofs(&proofs, &accumulator),
+        Err(vec![1])
+    );
+}
+
+// Tests that adding users in a batch issues the same witnesses as adding them one by one
+#[test]
+fn add_batch() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut single = server.clone();
+    let ids: Vec<UserID> = (0..10).map(|_| UserID::random()).collect();
+
+    let witnesses = server.add_batch(&ids).unwrap();
+    for (id, witness) in ids.iter().zip(witnesses.iter()) {
+        assert_eq!(single.add(*id), Some(*witness));
+    }
+    assert_eq!(
+        server.add_batch(&ids[..1]),
+        Err("user has already been added")
+    );
+    let new_id = UserID::random();
+    assert_eq!(
+        server.add_batch(&[new_id, new_id]),
+        Err("user has already been added")
+    );
+    assert!(!server.all_users.contains(&new_id));
+
+    // Deleting sweeps every remaining witness
+    server.delete(ids[0]);
+    let accumulator = server.get_accumulator();
+    let public_keys = server.get_public_keys();
+    for id in &ids[1..] {
+        let witness = server.all_witnesses[id];
+        assert!(witness.verify(*id, public_keys.witness_key, accumulator));
+    }
+}
+
+// Tests that cached update tables give the same responses as building them again
+#[test]
+fn cached_update_tables() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..12 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+    }
+    for user in users.iter_mut() {
+        user.create_witness(&params, &server);
+    }
+    for user in &users[..7] {
+        server.delete(user.get_id());
+    }
+    assert!(server.update_tables.is_empty());
+
+    let y_shares: Vec<Scalar> = (0..3).map(|_| Element::random().0).collect();
+    // Chunks of 2 deletions, the largest power of two the 3 shares allow
+    let response = server.update(7, &y_shares).unwrap();
+    assert_eq!(response.0.len(), 4);
+    // Tables are only built for chunks that are requested again
+    assert!(server.update_tables.is_empty());
+    assert_eq!(server.update(7, &y_shares).unwrap(), response);
+    assert_eq!(server.update_tables.len(), 4);
+    assert!(server.update_tables.size() > 0);
+    assert_eq!(server.update(7, &y_shares).unwrap(), response);
+
+    // A clone starts without tables and computes the same responses
+    let fresh = server.clone();
+    assert!(fresh.update_tables.is_empty());
+    assert_eq!(fresh.update(7, &y_shares).unwrap(), response);
+
+    // Holders at other epochs share the aligned chunks
+    let shorter = server.update(5, &y_shares).unwrap();
+    assert_eq!(shorter.0.len(), 3);
+    assert_eq!(server.update_tables.len(), 4);
+    server.update_tables.clear();
+    assert_eq!(server.update(5, &y_shares).unwrap(), shorter);
+
+    // Holders with more shares get larger chunks, starting with a partial
+    // one up to the next chunk boundary
+    let y_shares: Vec<Scalar> = (0..5).map(|_| Element::random().0).collect();
+    assert_eq!(server.update(5, &y_shares).unwrap().0.len(), 2);
+
+    // The cached tables still update witnesses correctly
+    let servers = vec![server.clone(), server.clone(), server.clone()];
+    users[8].update(&servers, 2).unwrap();
+    assert!(users[8]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+}
+
+// Tests pruning the server history down to a checkpoint
+#[test]
+fn pruned_history() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..10 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+    }
+    for user in users.iter_mut() {
+        user.create_witness(&params, &server);
+    }
+    for user in &users[..3] {
+        server.delete(user.get_id());
+    }
+    let servers = vec![server.clone(), server.clone(), server.clone()];
+    users[7].update(&servers, 2).unwrap();
+    users[8].update(&servers, 2).unwrap();
+    for user in &users[3..6] {
+        server.delete(user.get_id());
+    }
+    assert_eq!(server.get_epoch(), 7);
+
+    let genesis = server.checkpoint;
+    let accumulators = server.accumulators.clone();
+    let deletions = server.deletions.clone();
+    assert!(server.prune(0).is_err());
+    let checkpoint = server.prune(4).unwrap();
+    assert_eq!(checkpoint.epoch, 4);
+    assert_eq!(checkpoint.accumulator, accumulators[3]);
+    assert_eq!(server.first_epoch(), 4);
+    assert_eq!(server.get_epoch(), 7);
+    assert_eq!(server.get_accumulator_at(3), None);
+    assert_eq!(server.get_accumulator_at(4), Some(accumulators[3]));
+    assert_eq!(server.get_accumulator_at(7), Some(server.get_accumulator()));
+    // Pruning less than what is left is a no-op
+    assert_eq!(server.prune(10).unwrap(), checkpoint);
+
+    // The checkpoint can be audited against the full history
+    let audited = (0..3).fold(genesis, |c, i| {
+        c.advance(&deletions[i], &accumulators[i + 1])
+    });
+    assert_eq!(audited, checkpoint);
+
+    // Holders older than the checkpoint must be re-issued a witness
+    let servers = vec![server.clone(), server.clone(), server.clone()];
+    assert_eq!(users[6].update(&servers, 2), Err("re-issue required"));
+    let y_shares = [Element::random().0];
+    assert_eq!(
+        server.update(server.deletions.len() + 1, &y_shares),
+        Err("re-issue required")
+    );
+    assert_eq!(
+        server.publish_split_updates_since(1, 2),
+        Err("re-issue required")
+    );
+    assert_eq!(
+        server.publish_revoked_set_updates_since(3),
+        Err("re-issue required")
+    );
+
+    // Holders within the retained history still update
+    users[7].update(&servers, 2).unwrap();
+    assert!(users[7]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+    let updates = server.publish_split_updates(2).unwrap();
+    assert_eq!(updates[0].epoch, 4);
+    users[8].apply_split_updates(&params, &updates).unwrap();
+    assert_eq!(users[8].epoch, 7);
+    assert!(users[8]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+
+    // The revoked set still covers the pruned deletions
+    let request = users[0].make_reissue_request(&params).unwrap();
+    assert!(server.non_membership_witness(&params, &request).is_err());
+    let request = users[9].make_reissue_request(&params).unwrap();
+    assert!(server.non_membership_witness(&params, &request).is_ok());
+}
+
+// Tests re-issuing witnesses to holders behind the pruned history
+#[test]
+fn reissue_witness() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..6 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+    }
+    for user in users.iter_mut() {
+        user.create_witness(&params, &server);
+    }
+    for user in &users[..3] {
+        server.delete(user.get_id());
+    }
+    server.prune(1).unwrap();
+    let servers = vec![server.clone(), server.clone(), server.clone()];
+    assert_eq!(users[3].update(&servers, 2), Err("re-issue required"));
+
+    // The long-term key and signature are kept
+    let secret_key = users[3].witness.as_ref().unwrap().secret_key.0;
+    let signature = users[3].witness.as_ref().unwrap().signature;
+    users[3].reissue_witness(&params, &server).unwrap();
+    assert_eq!(users[3].epoch, server.get_epoch());
+    assert!(users[3]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+    assert_eq!(users[3].witness.as_ref().unwrap().secret_key.0, secret_key);
+    assert_eq!(users[3].witness.as_ref().unwrap().signature, signature);
+
+    // Requests are blinded, so two requests do not share the signature
+    let request = users[4].make_reissue_request(&params).unwrap();
+    let other = users[4].make_reissue_request(&params).unwrap();
+    assert_ne!(request.blinded_signature, other.blinded_signature);
+    assert!(server.reissue(&params, &request).is_ok());
+
+    // A request for another ID or from a deleted user is refused
+    let mut forged = request;
+    forged.id = users[5].get_id();
+    assert_eq!(
+        server.reissue(&params, &forged),
+        Err("invalid re-issue request")
+    );
+    let deleted = users[0].make_reissue_request(&params).unwrap();
+    assert_eq!(
+        server.reissue(&params, &deleted),
+        Err("user is not a member")
+    );
+    assert!(users[0].reissue_witness(&params, &server).is_err());
+}
+
+// Tests rotating the server keys and migrating holders to them
+#[test]
+fn key_rotation() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = Vec::new();
+    for _ in 0..5 {
+        users.push(User::new(&server, UserID::random()));
+        server.add(users.last().unwrap().get_id());
+        users.last_mut().unwrap().create_witness(&params, &server);
+    }
+    server.delete(users[0].get_id());
+    let servers = vec![server.clone(), server.clone(), server.clone()];
+    users[1].update(&servers, 2).unwrap();
+    users[4].update(&servers, 2).unwrap();
+    let old_keys = server.get_public_keys();
+    let old_revoked_key = server.get_revoked_public_key();
+    let mut session = VerifierSession::new([4u8; 32], 600, 16);
+    let challenge = session.issue_challenge(0).unwrap();
+    let old_presentation = users[1]
+        .make_non_revocation_presentation("registry", &params, &old_keys, &challenge)
+        .unwrap();
+
+    let linkage = server.rotate_keys(&params, 1000).unwrap();
+    assert!(linkage.verify(&params).is_ok());
+    let mut tampered = linkage;
+    tampered.grace_until = 2000;
+    assert_eq!(tampered.verify(&params), Err("invalid key linkage"));
+    assert_eq!(linkage.old_epoch, 2);
+    assert_eq!(linkage.new_epoch, server.get_epoch());
+    assert_eq!(server.get_epoch(), 3);
+    assert_eq!(server.get_accumulator(), linkage.new_accumulator);
+    assert_ne!(
+        server.get_public_keys().accumulator_id(),
+        old_keys.accumulator_id()
+    );
+    assert!(linkage.accepts(&old_keys, 999));
+    assert!(!linkage.accepts(&old_keys, 1000));
+    assert!(linkage.accepts(&server.get_public_keys(), 1000));
+
+    // The revoked-set key is rotated too, and the revoked set rebuilt under it
+    assert_eq!(linkage.old_revoked_key, old_revoked_key);
+    assert_eq!(linkage.new_revoked_key, server.get_revoked_public_key());
+    assert_ne!(server.get_revoked_public_key(), old_revoked_key);
+    assert_eq!(
+        server.get_revoked_accumulator(),
+        Accumulator::with_elements(&server.revoked_secret_key, &[users[0].get_id()])
+    );
+
+    // The rotated keys rebuild the server with the same revoked-set key
+    let rebuilt = Server::from_keys(
+        &params,
+        server.witness_secret_key.clone(),
+        server.sign_secret_key.clone(),
+        server.revoked_secret_key.clone(),
+        server.get_accumulator(),
+    );
+    assert_eq!(
+        rebuilt.get_public_keys().accumulator_id(),
+        server.get_public_keys().accumulator_id()
+    );
+    assert_eq!(
+        rebuilt.get_revoked_public_key(),
+        server.get_revoked_public_key()
+    );
+
+    // Verifiers accept the old keys during the grace period
+    let current = server.publish("registry");
+    let old = server.retired_server().unwrap().publish("registry");
+    assert!(old_presentation.verify(&current, &params).is_err());
+    assert!(session
+        .verify_presentation_during_rotation(
+            &old_presentation,
+            &current,
+            &old,
+            &linkage,
+            &params,
+            500
+        )
+        .is_ok());
+    assert_eq!(
+        old_presentation.verify_during_rotation(&current, &old, &linkage, &params, 1000),
+        Err("grace period has ended")
+    );
+    assert_eq!(
+        old_presentation.verify_during_rotation(&current, &current, &linkage, &params, 500),
+        Err("key linkage does not match the registry")
+    );
+
+    // Deletions after the rotation also reach the old accumulator,
+    // so deleted holders no longer verify under the old keys
+    server.delete(users[4].get_id());
+    let old = server.retired_server().unwrap().publish("registry");
+    assert_eq!(old.epoch, 3);
+    let challenge = session.issue_challenge(500).unwrap();
+    let deleted = users[4]
+        .make_non_revocation_presentation("registry", &params, &old_keys, &challenge)
+        .unwrap();
+    assert!(deleted
+        .verify_during_rotation(&current, &old, &linkage, &params, 500)
+        .is_err());
+
+    // Holders keep updating under the old keys, even from before the rotation
+    let retired = server.retired_server().unwrap().clone();
+    let servers = vec![retired.clone(), retired.clone(), retired];
+    assert_eq!(users[4].update(&servers, 2), Err("user has been deleted"));
+    users[2].update(&servers, 2).unwrap();
+    assert_eq!(users[2].epoch, 3);
+    let challenge = session.issue_challenge(500).unwrap();
+    let presentation = users[2]
+        .make_non_revocation_presentation("registry", &params, &old_keys, &challenge)
+        .unwrap();
+    assert!(presentation
+        .verify_during_rotation(&current, &old, &linkage, &params, 500)
+        .is_ok());
+
+    // Holders migrate with their long-term key
+    let secret_key = users[1].witness.as_ref().unwrap().secret_key.0;
+    users[1].migrate_witness(&params, &server, 500).unwrap();
+    assert_eq!(users[1].witness.as_ref().unwrap().secret_key.0, secret_key);
+    assert_eq!(users[1].epoch, server.get_epoch());
+    assert!(users[1]
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+    let current = server.publish("registry");
+    let challenge = session.issue_challenge(500).unwrap();
+    let presentation = users[1]
+        .make_non_revocation_presentation(
+            "registry",
+            &params,
+            &server.get_public_keys(),
+            &challenge,
+        )
+        .unwrap();
+    assert!(presentation
+        .verify_during_rotation(&current, &old, &linkage, &params, 2000)
+        .is_ok());
+    assert!(users[1]
+        .create_non_membership_witness(&params, &server)
+        .is_ok());
+
+    // Forged, deleted and late migrations are refused
+    let mut forged = users[2].make_migration_request(&params).unwrap();
+    forged.id = users[3].get_id();
+    assert_eq!(
+        server.migrate(&params, &forged, 500).err(),
+        Some("invalid migration request")
+    );
+    assert_eq!(
+        users[0].migrate_witness(&params, &server, 500),
+        Err("user is not a member")
+    );
+    assert_eq!(
+        users[2].migrate_witness(&params, &server, 1000),
+        Err("grace period has ended")
+    );
+    assert!(users[3].migrate_witness(&params, &server, 999).is_ok());
+
+    // New holders are issued witnesses under the new keys
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness(&params, &server);
+    assert!(user
+        .check_witness(&params, &server.get_accumulator())
+        .is_ok());
+    assert!(server.delete(user.get_id()).is_some());
+
+    // The old keys are dropped once the grace period ends
+    server.end_grace_period();
+    assert!(server.retired_server().is_none());
+    assert_eq!(
+        users[2].migrate_witness(&params, &server, 500),
+        Err("no key rotation")
+    );
+}
+
+// Tests building servers and running the protocol from seeds
+#[test]
+fn deterministic_server() {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    let params = AccParams::default();
+    let server = Server::from_seed(&params, [7u8; 32]);
+    let same = Server::from_seed(&params, [7u8; 32]);
+    let other = Server::from_seed(&params, [8u8; 32]);
+    let id = server.get_public_keys().accumulator_id();
+    assert_eq!(same.get_public_keys().accumulator_id(), id);
+    assert_ne!(other.get_public_keys().accumulator_id(), id);
+    assert_eq!(same.get_accumulator(), server.get_accumulator());
+    assert_eq!(
+        same.get_revoked_public_key(),
+        server.get_revoked_public_key()
+    );
+
+    // A server can be rebuilt from its backed-up keys
+    let rebuilt = Server::from_keys(
+        &params,
+        server.witness_secret_key.clone(),
+        server.sign_secret_key.clone(),
+        server.revoked_secret_key.clone(),
+        server.get_accumulator(),
+    );
+    assert_eq!(rebuilt.get_public_keys().accumulator_id(), id);
+    assert_eq!(
+        rebuilt.get_revoked_public_key(),
+        server.get_revoked_public_key()
+    );
+    assert_eq!(
+        rebuilt.get_revoked_accumulator(),
+        server.get_revoked_accumulator()
+    );
+
+    assert_eq!(
+        Element::random_with_rng(ChaCha20Rng::from_seed([1u8; 32])),
+        Element::random_with_rng(ChaCha20Rng::from_seed([1u8; 32]))
+    );
+
+    // The same seeds give the same witnesses, shares and updates
+    let mut servers = [server, same];
+    let user_id = UserID::random();
+    let mut users = Vec::new();
+    for server in servers.iter_mut() {
+        let others: Vec<UserID> = (1..4u64).map(|i| Element(Scalar::from(i))).collect();
+        let mut rng = ChaCha20Rng::from_seed([2u8; 32]);
+        let mut user = User::new(server, user_id);
+        server.add(user_id);
+        for y in &others {
+            server.add(*y);
+        }
+        user.create_witness_with_rng(&params, server, &mut rng);
+        for y in &others {
+            server.delete(*y);
+        }
+        let update = user
+            .prepare_for_update_with_rng(
+                server.get_epoch(),
+                3,
+                2,
+                UpdateStrategy::default(),
+                &mut rng,
+            )
+            .unwrap();
+        users.push((user, update));
+    }
+    let (user, update) = &users[0];
+    let (copy, copy_update) = &users[1];
+    assert_eq!(
+        user.witness.as_ref().unwrap().signature,
+        copy.witness.as_ref().unwrap().signature
+    );
+    assert_eq!(update.y_shares, copy_update.y_shares);
+
+    let mut user = user.clone();
+    let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = update
+        .y_shares
+        .iter()
+        .map(|shares| servers[0].update(update.epoch_diff, shares).unwrap())
+        .collect();
+    let witness = user
+        .post_update(
+            user.witness.as_ref().unwrap().witness,
+            2,
+            &update.y_shares,
+            &update.y_values,
+            &dvs,
+        )
+        .unwrap();
+    user.witness.as_mut().unwrap().witness = witness;
+    assert!(user
+        .check_witness(&params, &servers[0].get_accumulator())
+        .is_ok());
+}
+
+#[test]
+fn rng_injection() {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    let rng = |seed: u8| ChaCha20Rng::from_seed([seed; 32]);
+    assert_eq!(
+        SecretKey::random_with_rng(rng(1)).0,
+        SecretKey::random_with_rng(rng(1)).0
+    );
+    assert_eq!(
+        Accumulator::random_with_rng(rng(1)),
+        Accumulator::random_with_rng(rng(1))
+    );
+    assert_ne!(
+        Accumulator::random_with_rng(rng(1)),
+        Accumulator::random_with_rng(rng(2))
+    );
+
+    let params = AccParams::default();
+    let mut server = Server::from_seed(&params, [3u8; 32]);
+    let mut user = User::new(&server, UserID::random());
+    server.add(user.get_id());
+    user.create_witness_with_rng(&params, &server, &mut rng(4));
+    let public_keys = server.get_public_keys();
+    let accumulator = server.get_accumulator();
+
+    // Challenges and proofs are reproducible from the same seed
+    let mut session = VerifierSession::new([1u8; 32], 60, 16);
+    let challenge = session.issue_challenge_with_rng(1000, rng(5)).unwrap();
+    assert_eq!(
+        VerifierSession::new([1u8; 32], 60, 16)
+            .issue_challenge_with_rng(1000, rng(5))
+            .unwrap(),
+        challenge
+    );
+    let proof = user
+        .make_membership_proof_with_rng(&params, &public_keys, &challenge, rng(6))
+        .unwrap();
+    let same = user
+        .make_membership_proof_with_rng(&params, &public_keys, &challenge, rng(6))
+        .unwrap();
+    let other = user
+        .make_membership_proof_with_rng(&params, &public_keys, &challenge, rng(7))
+        .unwrap();
+    assert_eq!(
+        postcard::to_stdvec(&proof).unwrap(),
+        postcard::to_stdvec(&same).unwrap()
+    );
+    assert_ne!(
+        postcard::to_stdvec(&proof).unwrap(),
+        postcard::to_stdvec(&other).unwrap()
+    );
+    assert!(Witness::check_membership_proof(
+        &proof,
+        &params,
+        &public_keys,
+        &accumulator,
+        &challenge
+    ));
+
+    let batchable = user
+        .make_batchable_membership_proof(&params, &public_keys, &challenge)
+        .unwrap();
+    let verifier = PreparedVerifier::new(&params, &public_keys);
+    assert!(verifier
+        .batch_check_membership_proofs_with_rng(&[(batchable, challenge)], &accumulator, rng(8))
+        .is_ok());
+
+    // Wallets sealed with the same seed are identical and still open
+    let key = [9u8; 32];
+    let wallet = user
+        .export_encrypted_with_rng(WalletKey::Key(&key), rng(10))
+        .unwrap();
+    assert_eq!(
+        user.export_encrypted_with_rng(WalletKey::Key(&key), rng(10))
+            .unwrap(),
+        wallet
+    );
+    let restored = User::import_encrypted(&wallet, WalletKey::Key(&key)).unwrap();
+    assert!(restored.check_witness(&params, &accumulator).is_ok());
+}
+
+// Welch's t-statistic between the running times of `run(false)` and
+// `run(true)`, interleaved at random. Samples above the 90th percentile are
+// dropped as noise. A large |t| means the time depends on the class
+fn timing_t_statistic(samples: usize, mut run: impl FnMut(bool)) -> f64 {
+    let mut times = [Vec::new(), Vec::new()];
+    for _ in 0..samples {
+        let class = rand::rngs::OsRng.next_u32() & 1 == 1;
+        let start = std::time::Instant::now();
+        run(class);
+        times[class as usize].push(start.elapsed().as_nanos() as f64);
+    }
+    let mut all: Vec<f64> = times.iter().flatten().copied().collect();
+    all.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let cutoff = all[all.len() * 9 / 10];
+    let stats: Vec<(f64, f64, f64)> = times
+        .iter()
+        .map(|t| {
+            let t: Vec<f64> = t.iter().copied().filter(|t| *t <= cutoff).collect();
+            let n = t.len() as f64;
+            let mean = t.iter().sum::<f64>() / n;
+            let var = t.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1.0);
+            (n, mean, var)
+        })
+        .collect();
+    (stats[0].1 - stats[1].1) / (stats[0].2 / stats[0].0 + stats[1].2 / stats[1].0).sqrt()
+}
+
+// The largest |t| still taken as constant-time. Leaky code scores far above it
+const TIMING_T_THRESHOLD: f64 = 10.0;
+
+// Tests that answering an update takes as long for low-weight y-shares as
+// for random ones, which the variable-time multi-scalar multiplication does not.
+// Timing-sensitive, run with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn test_update_shares_constant_time() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let ids: Vec<UserID> = (0..33).map(|_| UserID::random()).collect();
+    server.add_batch(&ids).unwrap();
+    for id in &ids[1..] {
+        server.quick_delete(*id);
+    }
+    let low_weight = vec![Scalar::ONE; 32];
+    let random: Vec<Scalar> = (0..32).map(|_| Element::random().0).collect();
+
+    let points: Vec<G1Projective> = server.accumulators[..32].iter().map(|a| a.0).collect();
+    let t = timing_t_statistic(2000, |class| {
+        let shares = if class { &random } else { &low_weight };
+        std::hint::black_box(G1Projective::sum_of_products(&points, shares));
+    });
+    assert!(t.abs() > TIMING_T_THRESHOLD, "t = {}", t);
+
+    // The first request evaluates directly, the later ones use a cached table
+    for _ in 0..2 {
+        let t = timing_t_statistic(2000, |class| {
+            let shares = if class { &random } else { &low_weight };
+            std::hint::black_box(server.update(32, shares).unwrap());
+        });
+        assert!(t.abs() < TIMING_T_THRESHOLD, "t = {}", t);
+    }
+}
+
+// Tests that preparing an update and proving membership take as long for
+// a low-weight user ID as for a random one.
+// Timing-sensitive, run with `cargo test --release -- --ignored`
+#[test]
+#[ignore]
+fn test_user_operations_constant_time() {
+    let params = AccParams::default();
+    let mut server = Server::new(&params);
+    let mut users = [
+        User::new(&server, Element(Scalar::ONE)),
+        User::new(&server, UserID::random()),
+    ];
+    for user in users.iter_mut() {
+        server.add(user.get_id());
+        user.create_witness(&params, &server);
+    }
+    let epoch = users[0].epoch + 64;
+    let t = timing_t_statistic(2000, |class| {
+        std::hint::black_box(
+            users[class as usize]
+                .prepare_for_update(epoch, 5, 3)
+                .unwrap(),
+        );
+    });
+    assert!(t.abs() < TIMING_T_THRESHOLD, "t = {}", t);
+
+    let public_keys = server.get_public_keys();
+    let challenge = [7u8; 2 * SECURITY_BYTES];
+    let t = timing_t_statistic(400, |class| {
+        std::hint::black_box(
+            users[class as usize]
+                .make_membership_proof(&params, &public_keys, &challenge)
+                .unwrap(),
+        );
+    });
+    assert!(t.abs() < TIMING_T_THRESHOLD, "t = {}", t);
+}
@@ -1,9 +1,22 @@
-use crate::accumulator::{Accumulator, Element, MembershipWitness, SecretKey};
+use crate::accumulator::{
+    evaluate_delta, Accumulator, Element, MembershipWitness, NonMembershipWitness, PublicKey,
+    SecretKey,
+};
 use blsful::inner_types::*;
 use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroizing;
 
-use super::{servers::Server, utils::*, witness::*};
+use super::{
+    presentation::NonRevocationPresentation,
+    rotation::{MigratedWitness, MigrationRequest},
+    servers::{ReissuedWitness, RevokedSetUpdate, Server, SplitUpdate, MAX_UPDATE_CHUNK},
+    utils::*,
+    wallet::*,
+    witness::*,
+};
 
 /// The data a user needs to track
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -18,6 +31,48 @@ pub struct User {
     pub public_keys: PublicKeys,
     /// the epoch when the witness was last known to be valid
     pub epoch: usize,
+    /// Witness that the ID is not in the server's revoked set
+    pub non_membership: Option<RevokedSetWitness>,
+}
+
+/// The serialization format version written by `User::to_bytes`
+pub const USER_FORMAT_VERSION: u8 = 1;
+
+/// The unversioned layout users were serialized with
+/// before they tracked a non-membership witness
+#[derive(Deserialize)]
+struct LegacyUser {
+    id: UserID,
+    witness: Option<Witness>,
+    accumulator: Accumulator,
+    public_keys: PublicKeys,
+    epoch: usize,
+}
+
+impl From<LegacyUser> for User {
+    fn from(legacy: LegacyUser) -> Self {
+        Self {
+            id: legacy.id,
+            witness: legacy.witness,
+            accumulator: legacy.accumulator,
+            public_keys: legacy.public_keys,
+            epoch: legacy.epoch,
+            non_membership: None,
+        }
+    }
+}
+
+/// A witness that a user's ID is not in a server's revoked-set accumulator
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct RevokedSetWitness {
+    /// The non-membership witness
+    pub witness: NonMembershipWitness,
+    /// The revoked-set accumulator the witness is valid for
+    pub accumulator: Accumulator,
+    /// The public key of the revoked-set accumulator
+    pub public_key: PublicKey,
+    /// The epoch of the accumulator
+    pub epoch: usize,
 }
 
 impl User {
@@ -29,6 +84,7 @@ impl User {
             accumulator: server.get_accumulator(),
             public_keys: server.get_public_keys(),
             epoch: server.get_epoch(),
+            non_membership: None,
         }
     }
 
@@ -62,6 +118,7 @@ impl User {
             accumulator,
             public_keys,
             epoch,
+            non_membership: None,
         }
     }
 
@@ -80,10 +137,20 @@ impl User {
     /// create a ZKPoK of this key, and ask the server given
     /// as an argument for a new witness and long-term signature
     pub fn create_witness(&mut self, params: &AccParams, server: &Server) {
-        let key = SecretKey::new(None);
+        self.create_witness_with_rng(params, server, &mut rand::rngs::OsRng)
+    }
+
+    /// Like `create_witness`, drawing the secret key and proof nonce from `rng`
+    pub fn create_witness_with_rng(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) {
+        let key = SecretKey::random_with_rng(&mut *rng);
         let user_pub_key = params.get_k1() * key.0;
         // Create a Schnorr proofq
-        let k = Element::random();
+        let k = Element::random_with_rng(&mut *rng);
         let k_point = params.get_k1() * k.0;
         let mut transcript = Transcript::new(b"user_signature_proof");
         transcript.append_message(b"user_pub_key", user_pub_key.to_bytes().as_ref());
@@ -109,6 +176,102 @@ impl User {
         }
     }
 
+    /// Obtains a current witness from the server for the existing long-term
+    /// secret key and signature, e.g. when the user is too far behind to
+    /// update or the server pruned the deletions since the user's epoch
+    pub fn reissue_witness(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+    ) -> Result<(), &'static str> {
+        let request = self.make_reissue_request(params)?;
+        let reissued = server.reissue(params, &request)?;
+        self.apply_reissued_witness(params, &reissued)
+    }
+
+    /// Creates the request for `Server::reissue`
+    pub fn make_reissue_request(&self, params: &AccParams) -> Result<ReissueRequest, &'static str> {
+        let witness = self.witness.as_ref().ok_or("No witness")?;
+        Ok(ReissueRequest::new(
+            witness,
+            &self.id,
+            params,
+            &self.public_keys,
+        ))
+    }
+
+    /// Replaces the membership witness with one re-issued by `Server::reissue`
+    pub fn apply_reissued_witness(
+        &mut self,
+        params: &AccParams,
+        reissued: &ReissuedWitness,
+    ) -> Result<(), &'static str> {
+        let mut witness = self.witness.clone().ok_or("No witness")?;
+        witness.witness = reissued.witness;
+        Witness::verify(
+            &reissued.accumulator,
+            &self.public_keys,
+            params,
+            &self.id,
+            &witness,
+        )?;
+        self.witness = Some(witness);
+        self.accumulator = reissued.accumulator;
+        self.epoch = reissued.epoch;
+        Ok(())
+    }
+
+    /// Moves the user to the server's keys after a key rotation, keeping
+    /// the long-term secret key but obtaining a new witness and signature
+    pub fn migrate_witness(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        let request = self.make_migration_request(params)?;
+        let migrated = server.migrate(params, &request, now)?;
+        self.apply_migrated_witness(params, &migrated)
+    }
+
+    /// Creates the request for `Server::migrate`
+    pub fn make_migration_request(
+        &self,
+        params: &AccParams,
+    ) -> Result<MigrationRequest, &'static str> {
+        let witness = self.witness.as_ref().ok_or("No witness")?;
+        Ok(MigrationRequest::new(
+            witness,
+            &self.id,
+            params,
+            &self.public_keys,
+        ))
+    }
+
+    /// Replaces the witness, signature and public keys with the ones
+    /// issued by `Server::migrate`
+    pub fn apply_migrated_witness(
+        &mut self,
+        params: &AccParams,
+        migrated: &MigratedWitness,
+    ) -> Result<(), &'static str> {
+        let mut witness = self.witness.clone().ok_or("No witness")?;
+        witness.witness = migrated.witness;
+        witness.signature = migrated.signature;
+        Witness::verify(
+            &migrated.accumulator,
+            &migrated.public_keys,
+            params,
+            &self.id,
+            &witness,
+        )?;
+        self.witness = Some(witness);
+        self.accumulator = migrated.accumulator;
+        self.public_keys = migrated.public_keys;
+        self.epoch = migrated.epoch;
+        Ok(())
+    }
+
     /// Prepares the secret shares that will be sent to each server
     /// during the ALLOSAUR update
     pub fn prepare_for_update(
@@ -117,23 +280,60 @@ impl User {
         num_servers: usize,
         threshold: usize,
     ) -> Result<UserUpdate, &'static str> {
-        if num_servers < threshold {
-            return Err("invalid threshold");
-        }
-        if threshold <= 1 {
-            return Err("invalid threshold");
-        }
+        self.prepare_for_update_with_strategy(
+            new_epoch,
+            num_servers,
+            threshold,
+            UpdateStrategy::default(),
+        )
+    }
 
-        let d = new_epoch - self.epoch;
-        let mut k = ((d as f64) * 2.5).sqrt() as usize;
+    /// Reports the expected cost of updating to `new_epoch`
+    /// with the given strategy, without doing any of the work
+    pub fn update_cost(
+        &self,
+        new_epoch: usize,
+        num_servers: usize,
+        threshold: usize,
+        strategy: UpdateStrategy,
+    ) -> Result<UpdateCost, &'static str> {
+        check_threshold(num_servers, threshold)?;
+        let d = new_epoch.checked_sub(self.epoch).ok_or("invalid epoch")?;
+        Ok(UpdateCost::new(d, num_servers, threshold, strategy))
+    }
 
-        // We expect 32*(k-1) bytes user->server
-        // and 80*ceil(d/k) bytes server->user
-        // We want these to balance, with preference for user->server since it saves
-        // the user elliptic curve computations
-        while 2 * k < 5 * (d + k - 1) / k {
-            k += 1;
-        }
+    /// Prepares the secret shares that will be sent to each server
+    /// during the ALLOSAUR update, choosing the chunk size with `strategy`
+    pub fn prepare_for_update_with_strategy(
+        &self,
+        new_epoch: usize,
+        num_servers: usize,
+        threshold: usize,
+        strategy: UpdateStrategy,
+    ) -> Result<UserUpdate, &'static str> {
+        self.prepare_for_update_with_rng(
+            new_epoch,
+            num_servers,
+            threshold,
+            strategy,
+            &mut rand::rngs::OsRng,
+        )
+    }
+
+    /// Like `prepare_for_update_with_strategy`, drawing the Shamir
+    /// polynomials from `rng`
+    pub fn prepare_for_update_with_rng(
+        &self,
+        new_epoch: usize,
+        num_servers: usize,
+        threshold: usize,
+        strategy: UpdateStrategy,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<UserUpdate, &'static str> {
+        check_threshold(num_servers, threshold)?;
+
+        let d = new_epoch.checked_sub(self.epoch).ok_or("invalid epoch")?;
+        let k = strategy.chunk_size(d);
 
         // Create y, y^2, ..,. y^k-1
         let mut y_power = self.id.0;
@@ -142,14 +342,14 @@ impl User {
         let mut y_values = Vec::with_capacity(num_servers);
         let mut y_shares = Vec::with_capacity(2 * num_servers * k);
         // Create all keys in the hashmap from splitting the first power of y
-        for (value, share) in shamir_share(threshold, num_servers, y_power) {
+        for (value, share) in shamir_share(threshold, num_servers, y_power, rng) {
             y_shares.push(vec![share]);
             y_values.push(value);
         }
         // Add to all vectors in the hash map
         for _ in 1..k - 1 {
             y_power *= self.id.0; // = y^{i+1}
-            for (i, (_, share)) in shamir_share(threshold, num_servers, y_power)
+            for (i, (_, share)) in shamir_share(threshold, num_servers, y_power, rng)
                 .iter()
                 .enumerate()
             {
@@ -204,52 +404,56 @@ impl User {
         // We save on Shamir share reconstruction because we reconstruct all the secrets with the
         // same coefficients
         let (coefficients, check_coefficients) =
-            shamir_coefficients(threshold, &d_chunks_shares[0]);
+            shamir_coefficients(threshold, &d_chunks_shares[0])?;
         // Iterates through all the shares of all the chunks, reconstructs the chunk from the shares,
         // then adds this to the polynomials d and v
         // Since v_chunks_shares and d_chunks_shares have the same length, we iterate simultaneously
         let mut new_witness = old_witness;
-        let mut d_test = Scalar::ONE;
+        // A zero chunk means the user was deleted. Keep going regardless so the
+        // running time does not reveal which chunk contained the deletion
+        let mut deleted = Choice::from(0u8);
         for (i, shares_of_d_chunk) in d_chunks_shares.iter().enumerate() {
             // Through all shares, just rebuild
-            match shamir_rebuild_scalar(shares_of_d_chunk, &coefficients, &check_coefficients) {
-                Some(d_chunk) => {
-                    if d_chunk.is_zero().into() {
-                        return Err("user has been deleted");
-                    } // user was deleted!
-                    d_test *= d_chunk;
-                    match shamir_rebuild_point(
-                        &v_chunks_shares[i],
-                        &coefficients,
-                        &check_coefficients,
-                    ) {
-                        Some(v_chunk) => {
-                            // Note that d and v are not just chunks of an update of size k
-                            new_witness = MembershipWitness(
-                                (new_witness.0 - v_chunk) * d_chunk.invert().unwrap(),
-                            );
-                        }
-                        None => {
-                            return Err("malicious server");
-                        } // update failed!
-                    }
-                }
-                None => {
-                    return Err("malicious server");
-                } // update failed!
-                  // Failed update implies a malfunctioning/malicious server
-                  // The real protocol should start posting blame messages
-            }
+            // Failed update implies a malfunctioning/malicious server
+            // The real protocol should start posting blame messages
+            let d_chunk =
+                shamir_rebuild_scalar(shares_of_d_chunk, &coefficients, &check_coefficients)
+                    .ok_or("malicious server")?;
+            let v_chunk =
+                shamir_rebuild_point(&v_chunks_shares[i], &coefficients, &check_coefficients)
+                    .ok_or("malicious server")?;
+            let d_inv = d_chunk.invert();
+            deleted |= d_inv.is_none();
+            // Note that d and v are not just chunks of an update of size k
+            new_witness =
+                MembershipWitness((new_witness.0 - v_chunk) * d_inv.unwrap_or(Scalar::ZERO));
+        }
+        if deleted.into() {
+            return Err("user has been deleted");
         }
         Ok(new_witness)
     }
 
-
     /// Updates to the latest available epoch, from a set of servers
     pub fn update(&mut self, servers: &[Server], threshold: usize) -> Result<(), &'static str> {
+        self.update_with_strategy(servers, threshold, UpdateStrategy::default())
+    }
+
+    /// Updates to the latest available epoch, from a set of servers,
+    /// choosing the chunk size with `strategy`
+    pub fn update_with_strategy(
+        &mut self,
+        servers: &[Server],
+        threshold: usize,
+        strategy: UpdateStrategy,
+    ) -> Result<(), &'static str> {
         if self.witness.is_none() {
             return Err("No witness");
         }
+        // The servers no longer keep the deletions since our epoch
+        if self.epoch < servers[0].first_epoch() {
+            return Err("re-issue required");
+        }
         // // Check that current witness is valid
         // let acc = server.get_accumulator();
         // if !ver(self.accumulator, self.wit_public_key, self.sign_public_key, params, self.id, self.witness){
@@ -258,19 +462,23 @@ impl User {
         // If so, attempt update
 
         // Precompute shares
-        let (d, y_shares, y_values) =
-            match self.prepare_for_update(servers[0].get_epoch(), servers.len(), threshold) {
-                Ok(UserUpdate {
-                    epoch_diff,
-                    y_shares,
-                    y_values,
-                }) => (epoch_diff, y_shares, y_values),
-                Err(e) => return Err(e),
-            };
+        let (d, y_shares, y_values) = match self.prepare_for_update_with_strategy(
+            servers[0].get_epoch(),
+            servers.len(),
+            threshold,
+            strategy,
+        ) {
+            Ok(UserUpdate {
+                epoch_diff,
+                y_shares,
+                y_values,
+            }) => (epoch_diff, y_shares, y_values),
+            Err(e) => return Err(e),
+        };
         // Get answer from each server (directly)
         let dvs: Vec<(Vec<Scalar>, Vec<G1Projective>)> = (0..servers.len())
             .map(|i| servers[i].update(d, &y_shares[i]))
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         // Post-processes the update and returns the witness
         match self.post_update(
@@ -286,12 +494,48 @@ impl User {
                 }
 
                 self.accumulator = servers[0].get_accumulator();
+                self.epoch = servers[0].get_epoch();
                 Ok(())
             }
             Err(e) => Err(e),
         }
     }
 
+    /// Applies single-server split updates published with
+    /// `Server::publish_split_updates`. Slices the user has already
+    /// applied are skipped, the remaining ones must start at the user's epoch.
+    /// The user is left unchanged unless the updated witness verifies
+    /// against the final accumulator
+    pub fn apply_split_updates(
+        &mut self,
+        params: &AccParams,
+        updates: &[SplitUpdate],
+    ) -> Result<(), &'static str> {
+        let mut witness = self.witness.clone().ok_or("No witness")?;
+        let mut epoch = self.epoch;
+        let mut accumulator = self.accumulator;
+        let additions: &[Element] = &[];
+        for update in updates {
+            if update.epoch + update.deletions.len() <= epoch {
+                continue;
+            }
+            if update.epoch != epoch {
+                return Err("update does not start at the user's epoch");
+            }
+            let delta = evaluate_delta(self.id, additions, &update.deletions, &update.coefficients)
+                .map_err(|_| "user has been deleted")?;
+            witness.witness.apply_delta_assign(delta);
+            epoch += update.deletions.len();
+            accumulator = update.accumulator;
+        }
+        Witness::verify(&accumulator, &self.public_keys, params, &self.id, &witness)
+            .map_err(|_| "updated witness is invalid")?;
+        self.witness = Some(witness);
+        self.epoch = epoch;
+        self.accumulator = accumulator;
+        Ok(())
+    }
+
     /// Constructs a membership proof as a byte string
     pub fn make_membership_proof(
         &self,
@@ -313,6 +557,153 @@ impl User {
         }
     }
 
+    /// Like `make_membership_proof`, drawing the blinding factors from `rng`
+    pub fn make_membership_proof_with_rng(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+        rng: impl RngCore + CryptoRng,
+    ) -> Option<MembershipProof> {
+        Witness::make_membership_proof_with_rng(
+            self.witness.as_ref()?,
+            &self.id,
+            &self.accumulator,
+            params,
+            public_keys,
+            ephemeral_challenge,
+            rng,
+        )
+    }
+
+    /// Obtains a witness that the user's ID is not in the server's revoked set
+    pub fn create_non_membership_witness(
+        &mut self,
+        params: &AccParams,
+        server: &Server,
+    ) -> Result<(), &'static str> {
+        let request = self.make_reissue_request(params)?;
+        let witness = server.non_membership_witness(params, &request)?;
+        self.non_membership = Some(RevokedSetWitness {
+            witness,
+            accumulator: server.get_revoked_accumulator(),
+            public_key: server.get_revoked_public_key(),
+            epoch: server.get_epoch(),
+        });
+        Ok(())
+    }
+
+    /// Applies revoked-set updates published with
+    /// `Server::publish_revoked_set_updates_since`. Updates the user has
+    /// already applied are skipped, the remaining ones must start at the
+    /// epoch of the non-membership witness. The witness is left unchanged
+    /// if any update does not produce a valid witness
+    pub fn apply_revoked_set_updates(
+        &mut self,
+        updates: &[RevokedSetUpdate],
+    ) -> Result<(), &'static str> {
+        let mut state = self.non_membership.ok_or("No non-membership witness")?;
+        for update in updates {
+            if update.epoch < state.epoch {
+                continue;
+            }
+            if update.epoch != state.epoch {
+                return Err("update does not start at the user's epoch");
+            }
+            if bool::from(update.deletion.0.ct_eq(&self.id.0)) {
+                return Err("user has been deleted");
+            }
+            state.witness.update_assign(
+                self.id,
+                state.accumulator,
+                update.accumulator,
+                &[update.deletion],
+                &[],
+            );
+            state.accumulator = update.accumulator;
+            state.epoch += 1;
+            if !state
+                .witness
+                .verify(self.id, state.public_key, state.accumulator)
+            {
+                return Err("updated non-membership witness is invalid");
+            }
+        }
+        self.non_membership = Some(state);
+        Ok(())
+    }
+
+    /// Proves that the user's ID is not in the server's revoked set
+    pub fn make_non_membership_proof(
+        &self,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<NonMembershipProof> {
+        let state = self.non_membership.as_ref()?;
+        NonMembershipProof::new(
+            &self.id,
+            &state.witness,
+            &state.accumulator,
+            &state.public_key,
+            ephemeral_challenge,
+        )
+    }
+
+    /// Constructs a membership proof bound to the user's epoch
+    pub fn make_epoch_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<EpochMembershipProof> {
+        Witness::make_epoch_membership_proof(
+            self.witness.as_ref()?,
+            &self.id,
+            &self.accumulator,
+            self.epoch,
+            params,
+            public_keys,
+            ephemeral_challenge,
+        )
+    }
+
+    /// Constructs a self-describing presentation of the user's membership
+    /// in the registry `registry_id` at the user's epoch
+    pub fn make_non_revocation_presentation(
+        &self,
+        registry_id: &str,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<NonRevocationPresentation> {
+        NonRevocationPresentation::new(
+            self.witness.as_ref()?,
+            &self.id,
+            &self.accumulator,
+            self.epoch,
+            registry_id,
+            params,
+            public_keys,
+            ephemeral_challenge,
+        )
+    }
+
+    /// Constructs a membership proof that verifiers can check in a batch
+    pub fn make_batchable_membership_proof(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<BatchableMembershipProof> {
+        Witness::make_batchable_membership_proof(
+            self.witness.as_ref()?,
+            &self.id,
+            &self.accumulator,
+            params,
+            public_keys,
+            ephemeral_challenge,
+        )
+    }
+
     /// Checks whether the user has a valid witness for the given accumulator
     /// Compares to the user's internal copy of the signature key
     pub fn check_witness(
@@ -328,6 +719,41 @@ impl User {
             None => Err("no witness"),
         }
     }
+
+    /// Serializes and encrypts this user so the holder can back up
+    /// their witness, secret key and signature
+    pub fn export_encrypted(&self, key: WalletKey) -> Result<Vec<u8>, &'static str> {
+        self.export_encrypted_with_rng(key, rand::rngs::OsRng)
+    }
+
+    /// Like `export_encrypted`, drawing the salt and nonce from `rng`
+    pub fn export_encrypted_with_rng(
+        &self,
+        key: WalletKey,
+        rng: impl RngCore + CryptoRng,
+    ) -> Result<Vec<u8>, &'static str> {
+        let plaintext = Zeroizing::new(self.to_bytes()?);
+        seal_wallet(&plaintext, key, rng)
+    }
+
+    /// Decrypts and deserializes a user created by `export_encrypted`
+    pub fn import_encrypted(input: &[u8], key: WalletKey) -> Result<Self, &'static str> {
+        let plaintext = open_wallet(input, key)?;
+        Self::from_bytes(&plaintext)
+    }
+
+    /// Serializes this user, prefixed with `USER_FORMAT_VERSION`
+    pub fn to_bytes(&self) -> Result<Vec<u8>, &'static str> {
+        to_versioned_bytes(USER_FORMAT_VERSION, self)
+    }
+
+    /// Deserializes a user written by `to_bytes`, or by earlier
+    /// versions of this crate without a version byte
+    pub fn from_bytes(input: &[u8]) -> Result<Self, &'static str> {
+        from_versioned_bytes(USER_FORMAT_VERSION, input)
+            .or_else(|| from_all_bytes::<LegacyUser>(input).map(Self::from))
+            .ok_or("Invalid byte sequence")
+    }
 }
 
 /// A user update message
@@ -339,4 +765,115 @@ pub struct UserUpdate {
     pub y_shares: Vec<Vec<Scalar>>,
     /// The powers of the user's ID to be retained
     pub y_values: Vec<Scalar>,
-}
\ No newline at end of file
+}
+
+/// How the user trades bandwidth against computation
+/// when splitting an update into chunks of k-1 deletions
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum UpdateStrategy {
+    /// Balance 32*(k-1) bytes user->server against 80*ceil(d/(k-1)) bytes
+    /// server->user, with preference for user->server
+    #[default]
+    Balanced,
+    /// Send a single share to each server, k = 2
+    MinimizeUpload,
+    /// Ask for a single chunk so the user does the fewest elliptic curve operations
+    MinimizeUserOperations,
+    /// Use the given k, values below 2 are treated as 2
+    Fixed(usize),
+}
+
+impl UpdateStrategy {
+    /// The chunk size k for an update spanning `epoch_diff` epochs.
+    /// k-1 is rounded down to a power of two of at most `MAX_UPDATE_CHUNK`,
+    /// since `Server::update` would not use more shares
+    pub fn chunk_size(&self, epoch_diff: usize) -> usize {
+        let d = epoch_diff;
+        let k = match self {
+            Self::Balanced => {
+                if d == 0 {
+                    return 2;
+                }
+                let mut k = ((d as f64) * 2.5).sqrt() as usize;
+                // We expect 32*(k-1) bytes user->server
+                // and 80*ceil(d/(k-1)) bytes server->user
+                // We want these to balance, with preference for user->server since it saves
+                // the user elliptic curve computations
+                while 2 * k < 5 * (d + k - 1) / k {
+                    k += 1;
+                }
+                k
+            }
+            Self::MinimizeUpload => 2,
+            Self::MinimizeUserOperations => d + 1,
+            Self::Fixed(k) => *k,
+        };
+        let shares = (k.max(2) - 1).min(MAX_UPDATE_CHUNK);
+        (1 << (usize::BITS - 1 - shares.leading_zeros())) + 1
+    }
+}
+
+/// The expected cost of an ALLOSAUR update
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct UpdateCost {
+    /// The number of epochs being updated
+    pub epoch_diff: usize,
+    /// The chunk size k, each chunk covers k-1 deletions
+    pub chunk_size: usize,
+    /// The number of chunks each server returns to a holder whose epoch
+    /// is at a chunk boundary, others get one more
+    pub num_chunks: usize,
+    /// Bytes the user sends, summed over all servers
+    pub upload_bytes: usize,
+    /// Bytes the user receives, summed over all servers
+    pub download_bytes: usize,
+    /// Scalar multiplications in G1 the user performs in `post_update`
+    pub user_group_operations: usize,
+    /// Scalar multiplications in G1 each server performs in `Server::update`
+    pub server_group_operations: usize,
+}
+
+impl UpdateCost {
+    /// The size of a Shamir share of a power of y
+    const SHARE_BYTES: usize = 32;
+    /// The size of a (d, v) chunk returned by a server
+    const CHUNK_BYTES: usize = 32 + 48;
+
+    /// Estimates the cost of updating over `epoch_diff` epochs
+    pub fn new(
+        epoch_diff: usize,
+        num_servers: usize,
+        threshold: usize,
+        strategy: UpdateStrategy,
+    ) -> Self {
+        let k = strategy.chunk_size(epoch_diff);
+        let num_chunks = epoch_diff.div_ceil(k - 1);
+        // Each chunk is rebuilt from a threshold of shares, then checked
+        // against one more share when there are spare servers,
+        // then applied to the witness
+        let checks = if num_servers > threshold {
+            threshold
+        } else {
+            0
+        };
+        Self {
+            epoch_diff,
+            chunk_size: k,
+            num_chunks,
+            upload_bytes: num_servers * Self::SHARE_BYTES * (k - 1),
+            download_bytes: num_servers * Self::CHUNK_BYTES * num_chunks,
+            user_group_operations: num_chunks * (threshold + checks + 1),
+            server_group_operations: epoch_diff,
+        }
+    }
+}
+
+fn check_threshold(num_servers: usize, threshold: usize) -> Result<(), &'static str> {
+    if num_servers < threshold {
+        return Err("invalid threshold");
+    }
+    if threshold <= 1 {
+        return Err("invalid threshold");
+    }
+    Ok(())
+}
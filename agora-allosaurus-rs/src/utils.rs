@@ -1,7 +1,11 @@
-use crate::accumulator::{Element, PublicKey};
-use blsful::{inner_types::*, vsss_rs::Polynomial as VSSSPolynomial};
+use crate::accumulator::{batch_invert, Element, PublicKey};
+use blsful::inner_types::*;
 use merlin::Transcript;
-use serde::{Deserialize, Serialize};
+use rand::{CryptoRng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::sync::OnceLock;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
 
 /// The security parameter for the system
 pub(crate) const SECURITY_BYTES: usize = 128 / 8;
@@ -9,6 +13,29 @@ pub(crate) const SECURITY_BYTES: usize = 128 / 8;
 /// The UserID type
 pub type UserID = Element;
 
+/// Domain separation tag for deriving a [`UserID`] from a credential identifier
+pub const CREDENTIAL_ID_DST: &[u8] = b"ALLOSAUR-USERID-FROM-CREDENTIAL-ID-V1";
+
+impl Element {
+    /// Deterministically derives the user ID for the credential with
+    /// revocation index `cred_rev_id` in the registry `registry_id`.
+    ///
+    /// The ID is `generate_fr(SALT, DST || len(registry_id) || registry_id
+    /// || len(cred_rev_id) || cred_rev_id)` where lengths are 4-byte big-endian
+    /// and the 64-byte SHAKE-128 output is reduced as a little-endian integer.
+    /// `agora-allosaurus-py/allosaur/user_id.py` implements the same derivation
+    pub fn from_credential_id(registry_id: &str, cred_rev_id: &str) -> UserID {
+        let mut ikm =
+            Vec::with_capacity(CREDENTIAL_ID_DST.len() + 8 + registry_id.len() + cred_rev_id.len());
+        ikm.extend_from_slice(CREDENTIAL_ID_DST);
+        for part in [registry_id.as_bytes(), cred_rev_id.as_bytes()] {
+            ikm.extend_from_slice(&(part.len() as u32).to_be_bytes());
+            ikm.extend_from_slice(part);
+        }
+        Element::hash(&ikm)
+    }
+}
+
 /// ALLOSAUR public keys
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub struct PublicKeys {
@@ -18,6 +45,21 @@ pub struct PublicKeys {
     pub sign_key: PublicKey,
 }
 
+/// Identifies an accumulator by its public keys
+pub type AccumulatorId = [u8; 32];
+
+impl PublicKeys {
+    /// The identifier of the accumulator managed with these keys
+    pub fn accumulator_id(&self) -> AccumulatorId {
+        let mut transcript = Transcript::new(b"allosaur_accumulator_id");
+        transcript.append_message(b"Witness Public Key", self.witness_key.to_bytes().as_ref());
+        transcript.append_message(b"Signature Public Key", self.sign_key.to_bytes().as_ref());
+        let mut id = [0u8; 32];
+        transcript.challenge_bytes(b"accumulator id", &mut id);
+        id
+    }
+}
+
 /// Group parameters
 #[derive(Copy, Clone, Debug, Deserialize, Serialize, Eq, PartialEq, Hash)]
 pub struct AccParams {
@@ -32,7 +74,16 @@ pub struct AccParams {
 }
 
 impl Default for AccParams {
+    /// The standard parameters, computed once and cached
     fn default() -> AccParams {
+        static DEFAULT: OnceLock<AccParams> = OnceLock::new();
+        *DEFAULT.get_or_init(AccParams::generate)
+    }
+}
+
+impl AccParams {
+    /// Hashes to the standard generators
+    fn generate() -> AccParams {
         const DST_G1: &[u8] = b"BLS12381G1_XMD:SHA-256_SSWU_RO_";
         const DST_G2: &[u8] = b"BLS12381G2_XMD:SHA-256_SSWU_RO_";
         let mut array = [0xFFu8; 32];
@@ -58,9 +109,7 @@ impl Default for AccParams {
             z1,
         }
     }
-}
 
-impl AccParams {
     // read-only
     /// Get the p1 Generator
     pub fn get_p1(&self) -> G1Projective {
@@ -108,66 +157,88 @@ impl AccParams {
 // The returned vector consists of (value, share)
 // such that the there is a degree-(threshold) polynomial p such that
 // p(value) = share
+// The other coefficients of p are drawn from rng
 pub(crate) fn shamir_share(
     threshold: usize,
     num_shares: usize,
     secret: Scalar,
+    rng: &mut (impl RngCore + CryptoRng),
 ) -> Vec<(Scalar, Scalar)> {
-    let mut poly = Vec::<Scalar>::create(threshold);
+    let mut poly = vec![Scalar::ZERO; threshold];
     poly[0] = secret;
-    poly[1..].iter_mut().for_each(|x| *x = Element::random().0);
+    poly[1..]
+        .iter_mut()
+        .for_each(|x| *x = Element::random_with_rng(&mut *rng).0);
 
     let mut shares = vec![(Scalar::ZERO, Scalar::ZERO); num_shares];
     shares.iter_mut().enumerate().for_each(|(i, x)| {
         x.0 = Scalar::from((i + 1) as u64);
-        x.1 = poly.evaluate(x.0, threshold);
+        // Horner's method over every coefficient, whatever their values
+        x.1 = poly
+            .iter()
+            .rev()
+            .fold(Scalar::ZERO, |acc, coefficient| acc * x.0 + coefficient);
     });
+    // The coefficients reveal the secret, so don't leave them in memory
+    poly.zeroize();
     shares
 }
 
 // Produces just the coefficients necessary to rebuild from these shares
 // These save on computation because the user can build them once
+// Fails if two shares have the same value or a value is zero
 pub(crate) fn shamir_coefficients<T>(
     threshold: usize,
     shares: &[(Scalar, T)],
-) -> (Vec<Scalar>, Option<Vec<Scalar>>) {
+) -> Result<(Vec<Scalar>, Option<Vec<Scalar>>), &'static str> {
+    let has_check = shares.len() > threshold;
     let product = shares[0..threshold]
         .iter()
         .fold(Scalar::ONE, |a, y| a * y.0);
-    let mut coefficients = vec![product; threshold];
-    // Compact formula for coefficients to rebuild Shamir shares
+
+    // Every denominator is inverted at once: for each i, x_i and x_ii - x_i,
+    // then x_threshold - x_i for i > 0 for the check
+    let mut inverses = Vec::with_capacity(threshold * (threshold + 1));
     for i in 0..threshold {
         for ii in 0..threshold {
             if i == ii {
-                coefficients[i] *= shares[i].0.invert().expect("to not be zero");
+                inverses.push(shares[i].0);
             } else {
-                coefficients[i] *= (shares[ii].0 - shares[i].0)
-                    .invert()
-                    .expect("to not be zero");
+                inverses.push(shares[ii].0 - shares[i].0);
             }
         }
     }
+    if has_check {
+        for share in &shares[1..threshold] {
+            inverses.push(shares[threshold].0 - share.0);
+        }
+    }
+    batch_invert(&mut inverses).map_err(|_| "Shamir share values must be distinct and nonzero")?;
+
+    // Compact formula for coefficients to rebuild Shamir shares
+    let coefficients: Vec<Scalar> = inverses[..threshold * threshold]
+        .chunks(threshold)
+        .map(|row| row.iter().fold(product, |a, inv| a * inv))
+        .collect();
 
     // add a check
     // This is just a shift of the old shares, so there's less arithmetic to compute it
-    if shares.len() > threshold {
+    if has_check {
+        let check_inverses = &inverses[threshold * threshold..];
         let mut check_coefficients = coefficients.clone();
-        let adjustment = shares[threshold].0 * shares[0].0.invert().expect("to not be zero");
+        // inverses[0] is 1 / x_0
+        let adjustment = shares[threshold].0 * inverses[0];
         check_coefficients[0] = product * shares[0].0;
         for i in 1..threshold {
-            check_coefficients[i] *= adjustment
-                * (shares[0].0 - shares[i].0)
-                * (shares[threshold].0 - shares[i].0)
-                    .invert()
-                    .expect("to not be zero");
-            check_coefficients[0] *= (shares[i].0 - shares[threshold].0)
-                .invert()
-                .expect("to not be zero");
+            check_coefficients[i] *=
+                adjustment * (shares[0].0 - shares[i].0) * check_inverses[i - 1];
+            // 1 / (x_i - x_threshold)
+            check_coefficients[0] *= -check_inverses[i - 1];
         }
 
-        return (coefficients, Some(check_coefficients));
+        return Ok((coefficients, Some(check_coefficients)));
     }
-    (coefficients, None)
+    Ok((coefficients, None))
 }
 
 // Multiplies the coefficients by the returned shares to produce the output at 0
@@ -187,7 +258,7 @@ pub(crate) fn shamir_rebuild_scalar(
             for i in 1..threshold {
                 check_result += checks[i] * shares[i].1;
             }
-            if check_result == result {
+            if check_result.ct_eq(&result).into() {
                 return Some(result);
             }
             return None;
@@ -216,7 +287,7 @@ pub(crate) fn shamir_rebuild_point(
             for i in 1..threshold {
                 check_result += shares[i].1 * checks[i];
             }
-            if check_result == result {
+            if check_result.ct_eq(&result).into() {
                 return Some(result);
             }
             return None;
@@ -235,4 +306,29 @@ pub(crate) fn g1(b: &[u8]) -> Result<G1Projective, &'static str> {
 pub(crate) fn sc(b: &[u8]) -> Result<Scalar, &'static str> {
     let buf = <[u8; 32]>::try_from(b).map_err(|_| "Proof serialization error")?;
     Option::<Scalar>::from(Scalar::from_be_bytes(&buf)).ok_or("Proof serialization error")
-}
\ No newline at end of file
+}
+
+/// Serializes `value` behind a leading format `version` byte
+pub(crate) fn to_versioned_bytes<T: Serialize>(
+    version: u8,
+    value: &T,
+) -> Result<Vec<u8>, &'static str> {
+    postcard::to_extend(value, vec![version]).map_err(|_| "Invalid byte sequence")
+}
+
+/// Deserializes a value written by `to_versioned_bytes` with `version`,
+/// or `None` if `bytes` is not exactly such a value
+pub(crate) fn from_versioned_bytes<T: DeserializeOwned>(version: u8, bytes: &[u8]) -> Option<T> {
+    match bytes.split_first() {
+        Some((&v, rest)) if v == version => from_all_bytes(rest),
+        _ => None,
+    }
+}
+
+/// Deserializes a value that spans all of `bytes`
+pub(crate) fn from_all_bytes<T: DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    match postcard::take_from_bytes(bytes) {
+        Ok((value, [])) => Some(value),
+        _ => None,
+    }
+}
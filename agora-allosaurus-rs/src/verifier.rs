@@ -0,0 +1,197 @@
+use crate::accumulator::{Accumulator, PublicKey};
+use crate::presentation::{AccumulatorPublication, NonRevocationPresentation};
+use crate::rotation::KeyLinkage;
+use crate::utils::{AccParams, PublicKeys, SECURITY_BYTES};
+use crate::witness::{EpochMembershipProof, MembershipProof, NonMembershipProof, Witness};
+use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An ephemeral challenge handed to a prover
+pub type Challenge = [u8; 2 * SECURITY_BYTES];
+
+/// Issues the ephemeral challenges provers bind their membership proofs to
+/// and makes sure each challenge is only accepted once, before it expires.
+///
+/// Challenges are derived from the verifier's id and a fresh nonce, so a
+/// proof made for one verifier is never accepted by another. Times are
+/// seconds since the Unix epoch and are supplied by the caller.
+///
+/// At most `capacity` challenges are tracked at a time, counting both the
+/// outstanding ones and the redeemed ones that have not expired yet, so a
+/// session never holds more than `capacity` challenges per `ttl` seconds.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerifierSession {
+    /// Identifies the verifier the challenges are issued by
+    verifier_id: [u8; 32],
+    /// How long an issued challenge stays valid, in seconds
+    ttl: u64,
+    /// How many challenges can be tracked at once
+    capacity: usize,
+    /// Outstanding challenges and the time they expire
+    issued: HashMap<Challenge, u64>,
+    /// Redeemed challenges, kept until they would have expired
+    used: HashMap<Challenge, u64>,
+}
+
+impl VerifierSession {
+    /// Creates a session whose challenges expire `ttl` seconds after issuance
+    /// and that tracks at most `capacity` challenges at once
+    pub fn new(verifier_id: [u8; 32], ttl: u64, capacity: usize) -> Self {
+        Self {
+            verifier_id,
+            ttl,
+            capacity,
+            issued: HashMap::new(),
+            used: HashMap::new(),
+        }
+    }
+
+    /// The id challenges are bound to
+    pub fn verifier_id(&self) -> [u8; 32] {
+        self.verifier_id
+    }
+
+    /// Issues a fresh challenge that is valid until `now + ttl`.
+    /// Returns an error if the session already tracks `capacity` challenges
+    pub fn issue_challenge(&mut self, now: u64) -> Result<Challenge, &'static str> {
+        self.issue_challenge_with_rng(now, rand::rngs::OsRng)
+    }
+
+    /// Like `issue_challenge`, drawing the nonce from `rng`
+    pub fn issue_challenge_with_rng(
+        &mut self,
+        now: u64,
+        mut rng: impl RngCore + CryptoRng,
+    ) -> Result<Challenge, &'static str> {
+        self.prune(now);
+        if self.issued.len() + self.used.len() >= self.capacity {
+            return Err("too many outstanding challenges");
+        }
+        let expiry = now.saturating_add(self.ttl);
+        let mut nonce = [0u8; 32];
+        rng.fill_bytes(&mut nonce);
+
+        let mut transcript = Transcript::new(b"allosaur_verifier_challenge");
+        transcript.append_message(b"Verifier ID", &self.verifier_id);
+        transcript.append_u64(b"Expiry", expiry);
+        transcript.append_message(b"Nonce", &nonce);
+        let mut challenge = [0u8; 2 * SECURITY_BYTES];
+        transcript.challenge_bytes(b"challenge", &mut challenge);
+
+        self.issued.insert(challenge, expiry);
+        Ok(challenge)
+    }
+
+    /// Consumes an issued challenge. A challenge can only be redeemed once,
+    /// whether or not the proof presented with it verifies
+    pub fn redeem(&mut self, challenge: &Challenge, now: u64) -> Result<(), &'static str> {
+        if self.used.contains_key(challenge) {
+            return Err("challenge has already been used");
+        }
+        let expiry = self
+            .issued
+            .remove(challenge)
+            .ok_or("challenge was not issued by this verifier")?;
+        self.used.insert(*challenge, expiry);
+        if now >= expiry {
+            return Err("challenge has expired");
+        }
+        Ok(())
+    }
+
+    /// Redeems `challenge` and verifies a membership proof made with it
+    pub fn verify_membership_proof(
+        &mut self,
+        proof: &MembershipProof,
+        challenge: &Challenge,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        self.redeem(challenge, now)?;
+        if Witness::check_membership_proof(proof, params, public_keys, accumulator, challenge) {
+            Ok(())
+        } else {
+            Err("invalid membership proof")
+        }
+    }
+
+    /// Redeems `challenge` and verifies an epoch membership proof made with
+    /// it against one of the last `window` accumulators of `history`
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_epoch_membership_proof(
+        &mut self,
+        proof: &EpochMembershipProof,
+        challenge: &Challenge,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        history: &[(usize, Accumulator)],
+        window: usize,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        self.redeem(challenge, now)?;
+        Witness::check_membership_proof_in_window(
+            proof,
+            params,
+            public_keys,
+            history,
+            window,
+            challenge,
+        )
+    }
+
+    /// Redeems `challenge` and verifies a non-membership proof made with it
+    /// against the revoked-set accumulator and its public key
+    pub fn verify_non_membership_proof(
+        &mut self,
+        proof: &NonMembershipProof,
+        challenge: &Challenge,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        self.redeem(challenge, now)?;
+        if proof.verify(accumulator, public_key, challenge) {
+            Ok(())
+        } else {
+            Err("invalid non-membership proof")
+        }
+    }
+
+    /// Redeems the presentation's challenge and verifies it against the
+    /// trusted state of its registry
+    pub fn verify_presentation(
+        &mut self,
+        presentation: &NonRevocationPresentation,
+        trusted: &AccumulatorPublication,
+        params: &AccParams,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        self.redeem(&presentation.challenge, now)?;
+        presentation.verify(trusted, params)
+    }
+
+    /// Like `verify_presentation`, also accepting presentations made under
+    /// the old keys of `linkage` against `old` until its grace period ends
+    pub fn verify_presentation_during_rotation(
+        &mut self,
+        presentation: &NonRevocationPresentation,
+        current: &AccumulatorPublication,
+        old: &AccumulatorPublication,
+        linkage: &KeyLinkage,
+        params: &AccParams,
+        now: u64,
+    ) -> Result<(), &'static str> {
+        self.redeem(&presentation.challenge, now)?;
+        presentation.verify_during_rotation(current, old, linkage, params, now)
+    }
+
+    /// Forgets every challenge that expired before `now`
+    pub fn prune(&mut self, now: u64) {
+        self.issued.retain(|_, expiry| *expiry > now);
+        self.used.retain(|_, expiry| *expiry > now);
+    }
+}
@@ -0,0 +1,117 @@
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use rand::{CryptoRng, RngCore};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// The current version of the encrypted wallet format
+const WALLET_VERSION: u8 = 1;
+/// The number of bytes of KDF salt stored in the wallet header
+const SALT_BYTES: usize = 16;
+/// The number of bytes in an XChaCha20-Poly1305 nonce
+const NONCE_BYTES: usize = 24;
+/// version || kdf || salt || nonce
+const HEADER_BYTES: usize = 2 + SALT_BYTES + NONCE_BYTES;
+/// Domain separation for deriving the wallet key from a raw key
+const HKDF_INFO: &[u8] = b"ALLOSAUR-WALLET-KEY-V1";
+
+/// The secret used to protect an exported wallet
+///
+/// Passwords are stretched with Argon2id, raw keys are expanded with
+/// HKDF-SHA256. Either way a fresh salt is stored with the wallet.
+#[derive(Copy, Clone)]
+pub enum WalletKey<'a> {
+    /// A low entropy password chosen by the holder
+    Password(&'a [u8]),
+    /// A uniformly random 32-byte key, e.g. from a hardware keystore
+    Key(&'a [u8; 32]),
+}
+
+impl WalletKey<'_> {
+    fn tag(&self) -> u8 {
+        match self {
+            Self::Password(_) => 1,
+            Self::Key(_) => 2,
+        }
+    }
+
+    /// Derive the AEAD key for this wallet
+    fn derive(&self, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, &'static str> {
+        let mut okm = Zeroizing::new([0u8; 32]);
+        match self {
+            Self::Password(password) => Argon2::default()
+                .hash_password_into(password, salt, okm.as_mut())
+                .map_err(|_| "wallet key derivation failed")?,
+            Self::Key(key) => Hkdf::<Sha256>::new(Some(salt), key.as_ref())
+                .expand(HKDF_INFO, okm.as_mut())
+                .map_err(|_| "wallet key derivation failed")?,
+        }
+        Ok(okm)
+    }
+}
+
+/// Encrypts `plaintext` under `key`
+///
+/// The output is version || kdf || salt || nonce || ciphertext where the
+/// header is bound to the ciphertext as associated data.
+pub(crate) fn seal_wallet(
+    plaintext: &[u8],
+    key: WalletKey,
+    mut rng: impl RngCore + CryptoRng,
+) -> Result<Vec<u8>, &'static str> {
+    let mut header = [0u8; HEADER_BYTES];
+    header[0] = WALLET_VERSION;
+    header[1] = key.tag();
+    rng.fill_bytes(&mut header[2..]);
+
+    let wallet_key = key.derive(&header[2..2 + SALT_BYTES])?;
+    let cipher = XChaCha20Poly1305::new(wallet_key.as_ref().into());
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&header[2 + SALT_BYTES..]),
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|_| "wallet encryption failed")?;
+
+    let mut res = Vec::with_capacity(HEADER_BYTES + ciphertext.len());
+    res.extend_from_slice(&header);
+    res.extend_from_slice(&ciphertext);
+    Ok(res)
+}
+
+/// Decrypts a wallet created by [`seal_wallet`]
+pub(crate) fn open_wallet(
+    input: &[u8],
+    key: WalletKey,
+) -> Result<Zeroizing<Vec<u8>>, &'static str> {
+    if input.len() < HEADER_BYTES {
+        return Err("Invalid byte sequence");
+    }
+    let (header, ciphertext) = input.split_at(HEADER_BYTES);
+    if header[0] != WALLET_VERSION {
+        return Err("unsupported wallet version");
+    }
+    if header[1] != key.tag() {
+        return Err("wallet key type mismatch");
+    }
+
+    let wallet_key = key.derive(&header[2..2 + SALT_BYTES])?;
+    let cipher = XChaCha20Poly1305::new(wallet_key.as_ref().into());
+    cipher
+        .decrypt(
+            XNonce::from_slice(&header[2 + SALT_BYTES..]),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|_| "wallet decryption failed")
+}
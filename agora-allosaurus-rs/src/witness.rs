@@ -1,17 +1,21 @@
 use crate::accumulator::{
-    generate_fr, pair, schnorr, Accumulator, Element, MembershipWitness, SecretKey, SALT,
+    self, generate_fr, pair, schnorr, Accumulator, Element, MembershipWitness,
+    NonMembershipWitness, ProofMessage, ProofParams, PublicKey, SecretKey, SALT,
 };
 use crate::utils::{g1, sc};
 use blsful::inner_types::*;
 use merlin::Transcript;
+use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::fmt::{self, Display, Formatter};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use super::utils::{AccParams, PublicKeys, UserID, SECURITY_BYTES};
+use super::utils::{AccParams, AccumulatorId, PublicKeys, UserID, SECURITY_BYTES};
 
 /// Data type containing all the witness-related information a user needs
 /// (though they still need an accumulator to incorporate into a proof)
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Witness {
     /// The user's secret key
     pub secret_key: SecretKey,
@@ -21,6 +25,25 @@ pub struct Witness {
     pub signature: G1Projective,
 }
 
+impl fmt::Debug for Witness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Witness {{ secret_key: <redacted>, witness: {}, signature: <redacted> }}",
+            self.witness
+        )
+    }
+}
+
+impl Drop for Witness {
+    fn drop(&mut self) {
+        self.secret_key.zeroize();
+        self.signature.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Witness {}
+
 impl Witness {
     /// Verifies a witness directly, using the user's ID and their witness (including secret key)
     pub fn verify(
@@ -30,56 +53,7 @@ impl Witness {
         y: &UserID,
         witness: &Witness,
     ) -> Result<(), &'static str> {
-        // Follows the basic pattern of the two pairing checks
-        let first_check = multi_miller_loop(&[
-            (
-                &witness.witness.0.to_affine(),
-                &G2Prepared::from((params.get_p2() * y.0 + public_keys.witness_key.0).to_affine()),
-            ),
-            (
-                &accumulator.0.to_affine(),
-                &G2Prepared::from(-params.get_p2().to_affine()),
-            ),
-        ])
-        .final_exponentiation()
-        .is_identity();
-        // let lhs = pair(
-        //     witness.witness.0,
-        //     params.get_p2() * y.0 + public_keys.witness_key.0,
-        // );
-        // let rhs = pair(accumulator.0, params.get_p2());
-        // if lhs != rhs {
-        //     return Err("e()");
-        // }
-
-        let second_check = multi_miller_loop(&[
-            (
-                &witness.signature.to_affine(),
-                &G2Prepared::from((params.get_k2() * y.0 + public_keys.sign_key.0).to_affine()),
-            ),
-            (
-                &(params.get_k1() * witness.secret_key.0 + params.get_k0()).to_affine(),
-                &G2Prepared::from(-params.get_k2().to_affine()),
-            ),
-        ])
-        .final_exponentiation()
-        .is_identity();
-        // let lhs = pair(
-        //     witness.signature,
-        //     params.get_k2() * y.0 + public_keys.sign_key.0,
-        // );
-        // let rhs = pair(
-        //     params.get_k1() * witness.secret_key.0 + params.get_k0(),
-        //     params.get_k2(),
-        // );
-        // if lhs != rhs {
-        //     return false;
-        // }
-        if (first_check & second_check).into() {
-            Ok(())
-        } else {
-            Err("failed")
-        }
+        PreparedVerifier::new(params, public_keys).verify_witness(accumulator, y, witness)
     }
 
     /// Constructs a membership proof as a byte string
@@ -93,34 +67,111 @@ impl Witness {
         public_keys: &PublicKeys,
         ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
     ) -> Option<MembershipProof> {
-        // Check that witness is valid
-        Self::verify(accumulator, public_keys, params, user_id, witness).ok()?;
+        Self::make_batchable_membership_proof(
+            witness,
+            user_id,
+            accumulator,
+            params,
+            public_keys,
+            ephemeral_challenge,
+        )
+        .map(|proof| proof.proof)
+    }
 
+    /// Constructs a membership proof that also carries the pairing
+    /// commitments, so it can be checked with `batch_check_membership_proofs`
+    pub fn make_batchable_membership_proof(
+        witness: &Witness,
+        user_id: &UserID,
+        accumulator: &Accumulator,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<BatchableMembershipProof> {
+        Self::make_membership_proof_with_messages(
+            witness,
+            ProofMessage::Hidden { message: user_id.0 },
+            ProofMessage::Hidden {
+                message: witness.secret_key.0,
+            },
+            accumulator,
+            params,
+            public_keys,
+            ephemeral_challenge,
+        )
+    }
+
+    /// Constructs a membership proof where the user ID `y` and the secret key
+    /// can be given a shared blinding.
+    ///
+    /// An outer proof, e.g. of a CL or BBS+ credential, that uses the same
+    /// blinding and this proof's challenge produces the same response as
+    /// `MembershipProof::get_user_id_response` (or `get_secret_key_response`)
+    /// exactly when it hides the same value. Binding the outer proof's
+    /// commitments into `ephemeral_challenge` ties the two proofs together.
+    #[allow(clippy::too_many_arguments)]
+    pub fn make_membership_proof_with_messages(
+        witness: &Witness,
+        user_id: ProofMessage,
+        secret_key: ProofMessage,
+        accumulator: &Accumulator,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<BatchableMembershipProof> {
         // Create commitment
-        let mpc = MembershipProofCommitting::new(witness, params, public_keys);
+        let mpc = MembershipProofCommitting::new(
+            witness,
+            user_id,
+            secret_key,
+            accumulator,
+            params,
+            public_keys,
+        )?;
+        Some(Self::prove_membership(mpc, ephemeral_challenge))
+    }
 
-        // Commit to public parameters
+    /// Like `make_membership_proof`, drawing the blinding factors from `rng`
+    pub fn make_membership_proof_with_rng(
+        witness: &Witness,
+        user_id: &UserID,
+        accumulator: &Accumulator,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+        rng: impl RngCore + CryptoRng,
+    ) -> Option<MembershipProof> {
+        let mpc = MembershipProofCommitting::new_with_rng(
+            witness,
+            ProofMessage::Hidden { message: user_id.0 },
+            ProofMessage::Hidden {
+                message: witness.secret_key.0,
+            },
+            accumulator,
+            params,
+            public_keys,
+            rng,
+        )?;
+        Some(Self::prove_membership(mpc, ephemeral_challenge).proof)
+    }
+
+    /// Completes a basic membership proof from its commitment
+    fn prove_membership(
+        mpc: MembershipProofCommitting,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> BatchableMembershipProof {
+        // Commit to public parameters and the elements of the proof
         let mut transcript = Transcript::new(b"basic_membership_proof");
-        transcript.append_message(
-            b"Signature Public Key",
-            public_keys.witness_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(
-            b"Witness Public Key",
-            public_keys.sign_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
-        params.add_to_transcript(&mut transcript);
-        // Add the elements of the proof to the commitment
         mpc.get_bytes_for_challenge(&mut transcript);
         // Add the challenge
         transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
         // Create challenge hash
         let challenge = Element::from_transcript(b"challenge", &mut transcript);
         // Construct response and remove unnecessary elements of the commitment
-        let proof = mpc.gen_proof(witness, user_id, challenge);
+        let (pi_1, pi_2) = (mpc.pi_1, mpc.pi_2);
+        let proof = mpc.gen_proof(challenge);
 
-        Some(proof)
+        BatchableMembershipProof { proof, pi_1, pi_2 }
     }
 
     /// Verifies a ZKPoK membership proof given as byte string
@@ -131,25 +182,128 @@ impl Witness {
         accumulator: &Accumulator,
         ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
     ) -> bool {
-        // Construct commitments to public parameters/keys
+        // Commit to public parameters/keys, then reconstruct all necessary
+        // points and add them to the transcript
         let mut transcript = Transcript::new(b"basic_membership_proof");
-        transcript.append_message(
-            b"Signature Public Key",
-            public_keys.witness_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(
-            b"Witness Public Key",
-            public_keys.sign_key.to_bytes().as_ref(),
-        );
-        transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
-        params.add_to_transcript(&mut transcript);
-
-        // Reconstruct all necessary points and add them to the transcript
         proof.get_bytes_for_challenge(params, public_keys, accumulator, &mut transcript);
         transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
         // Verifies that the full reconstructed transcript matches the hash
         let challenge = Element::from_transcript(b"challenge", &mut transcript);
-        challenge.0 == proof.challenge
+        proof.check_challenge(challenge)
+    }
+
+    /// Constructs a membership proof that is bound to the accumulator's id
+    /// and the epoch of `accumulator`, so verifiers can look up the
+    /// accumulator the proof was made against
+    pub fn make_epoch_membership_proof(
+        witness: &Witness,
+        user_id: &UserID,
+        accumulator: &Accumulator,
+        epoch: usize,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<EpochMembershipProof> {
+        let mpc = MembershipProofCommitting::new(
+            witness,
+            ProofMessage::Hidden { message: user_id.0 },
+            ProofMessage::Hidden {
+                message: witness.secret_key.0,
+            },
+            accumulator,
+            params,
+            public_keys,
+        )?;
+
+        let accumulator_id = public_keys.accumulator_id();
+        let mut transcript = Transcript::new(b"epoch_membership_proof");
+        append_epoch(&accumulator_id, epoch, &mut transcript);
+        mpc.get_bytes_for_challenge(&mut transcript);
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+
+        Some(EpochMembershipProof {
+            accumulator_id,
+            epoch,
+            proof: mpc.gen_proof(challenge),
+        })
+    }
+
+    /// Verifies an epoch membership proof against the accumulator of its epoch
+    pub fn check_epoch_membership_proof(
+        proof: &EpochMembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        if proof.accumulator_id != public_keys.accumulator_id() {
+            return false;
+        }
+        let mut transcript = Transcript::new(b"epoch_membership_proof");
+        append_epoch(&proof.accumulator_id, proof.epoch, &mut transcript);
+        proof
+            .proof
+            .get_bytes_for_challenge(params, public_keys, accumulator, &mut transcript);
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        proof.proof.check_challenge(challenge)
+    }
+
+    /// Verifies an epoch membership proof made against any of the last
+    /// `window` epochs of `history`, a list of `(epoch, accumulator)` pairs
+    /// as returned by `Server::publish_accumulator_history`.
+    /// A window of 1 only accepts proofs for the latest accumulator
+    pub fn check_membership_proof_in_window(
+        proof: &EpochMembershipProof,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        history: &[(usize, Accumulator)],
+        window: usize,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Result<(), &'static str> {
+        if proof.accumulator_id != public_keys.accumulator_id() {
+            return Err("unknown accumulator");
+        }
+        let latest = history.iter().map(|(epoch, _)| *epoch).max().unwrap_or(0);
+        if proof.epoch == 0 || proof.epoch > latest {
+            return Err("unknown epoch");
+        }
+        if proof.epoch.saturating_add(window) <= latest {
+            return Err("proof epoch is outside the verification window");
+        }
+        let accumulator = history
+            .iter()
+            .find(|(epoch, _)| *epoch == proof.epoch)
+            .map(|(_, accumulator)| accumulator)
+            .ok_or("re-issue required")?;
+        if Self::check_epoch_membership_proof(
+            proof,
+            params,
+            public_keys,
+            accumulator,
+            ephemeral_challenge,
+        ) {
+            Ok(())
+        } else {
+            Err("invalid membership proof")
+        }
+    }
+
+    /// Verifies many membership proofs against the same accumulator and keys.
+    ///
+    /// Each transcript is rebuilt from the proof and its pairing commitments,
+    /// then every pairing equation is combined with random weights and checked
+    /// with a single multi-Miller loop. If the batch fails, each proof is
+    /// checked on its own and the indices of the failing proofs are returned.
+    pub fn batch_check_membership_proofs(
+        proofs: &[(BatchableMembershipProof, [u8; 2 * SECURITY_BYTES])],
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        accumulator: &Accumulator,
+    ) -> Result<(), Vec<usize>> {
+        PreparedVerifier::new(params, public_keys)
+            .batch_check_membership_proofs(proofs, accumulator)
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -177,40 +331,108 @@ impl Witness {
 
 /// The commit or blinding step for generating a ZKP
 /// The next step is to call `get_bytes_for_challenge`
-/// to create the fiat shamir heuristic
-#[derive(Debug, Copy, Clone)]
-struct MembershipProofCommitting {
-    pub r: [Scalar; 3],
-    pub k: [Scalar; 8],
-    pub u_1: G1Projective,
-    pub u_2: G1Projective,
-    pub r_point: G1Projective,
-    pub t_1: G1Projective,
-    pub t_2: G1Projective,
-    pub pi_1: Gt,
-    pub pi_2: Gt,
+/// to create the fiat shamir heuristic, then `gen_proof`
+/// with the resulting challenge.
+///
+/// The transcript can be shared with other proofs, e.g. of a credential,
+/// so that all of them are answered with a single challenge.
+///
+/// `gen_proof` consumes the commitment: answering two challenges with the
+/// same blinding factors would reveal the user ID and secret key
+pub struct MembershipProofCommitting {
+    r: [Scalar; 3],
+    k: [Scalar; 8],
+    x: Scalar,
+    y: Scalar,
+    u_1: G1Projective,
+    u_2: G1Projective,
+    r_point: G1Projective,
+    t_1: G1Projective,
+    t_2: G1Projective,
+    pi_1: Gt,
+    pi_2: Gt,
+    accumulator: Accumulator,
+    public_keys: PublicKeys,
+    params: AccParams,
+}
+
+impl fmt::Debug for MembershipProofCommitting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MembershipProofCommitting {{ u_1: {}, u_2: {}, r: {}, t_1: {}, t_2: {}, <secrets redacted> }}",
+            self.u_1, self.u_2, self.r_point, self.t_1, self.t_2
+        )
+    }
+}
+
+impl Drop for MembershipProofCommitting {
+    fn drop(&mut self) {
+        self.r.zeroize();
+        self.k.zeroize();
+        self.x.zeroize();
+        self.y.zeroize();
+    }
 }
 
 impl MembershipProofCommitting {
-    /// Create a new membership proof committing phase
+    /// Create a new membership proof committing phase.
+    /// Returns `None` if the witness is not valid for the accumulator
+    /// or does not belong to the hidden user ID and secret key
+    pub fn new(
+        witness: &Witness,
+        user_id: ProofMessage,
+        secret_key: ProofMessage,
+        accumulator: &Accumulator,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+    ) -> Option<Self> {
+        Self::new_with_rng(
+            witness,
+            user_id,
+            secret_key,
+            accumulator,
+            params,
+            public_keys,
+            rand::rngs::OsRng,
+        )
+    }
+
+    /// Like `new`, drawing the blinding factors from `rng`
     // Follows the ZKPoK in the PROVE function on page 88
-    pub fn new(witness: &Witness, params: &AccParams, public_keys: &PublicKeys) -> Self {
-        let rng = rand::rngs::OsRng;
-        // Randomly select r_1, r_2, r_3, k_0,..k_7
+    pub fn new_with_rng(
+        witness: &Witness,
+        user_id: ProofMessage,
+        secret_key: ProofMessage,
+        accumulator: &Accumulator,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        mut rng: impl RngCore + CryptoRng,
+    ) -> Option<Self> {
+        let x = secret_key.get_message();
+        let y = user_id.get_message();
+        Witness::verify(accumulator, public_keys, params, &Element(y), witness).ok()?;
+        if !bool::from(x.ct_eq(&witness.secret_key.0)) {
+            return None;
+        }
+
+        // Randomly select r_1, r_2, r_3, k_1,..k_6
+        // k_0 and k_y are either generated randomly or supplied in case this
+        // proof is used to bind to an external proof
         let r: [Scalar; 3] = [
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
         ];
         let k: [Scalar; 8] = [
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
-            generate_fr(SALT, None, rng),
+            secret_key.get_blinder(&mut rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
+            generate_fr(SALT, None, &mut rng),
+            user_id.get_blinder(&mut rng),
         ];
 
         // U_1 = R_m + r_1Y
@@ -243,9 +465,11 @@ impl MembershipProofCommitting {
         let pi_2 = pair(params.get_z1() * k[5] - u_2 * k[7], params.get_p2())
             + pair(params.get_z1(), public_keys.witness_key.0 * k[2]);
 
-        Self {
+        Some(Self {
             r,
             k,
+            x,
+            y,
             u_1,
             u_2,
             r_point,
@@ -253,13 +477,22 @@ impl MembershipProofCommitting {
             t_2,
             pi_1,
             pi_2,
-        }
+            accumulator: *accumulator,
+            public_keys: *public_keys,
+            params: *params,
+        })
     }
 
     /// Return bytes that need to be hashed for generating challenge.
     ///
-    /// U_1 || U_2 || R || T_1 || T_2 || Pi_1 || Pi_2
+    /// public inputs || U_1 || U_2 || R || T_1 || T_2 || Pi_1 || Pi_2
     pub fn get_bytes_for_challenge(&self, transcript: &mut Transcript) {
+        append_public_inputs(
+            &self.params,
+            &self.public_keys,
+            &self.accumulator,
+            transcript,
+        );
         transcript.append_message(b"U_1", &self.u_1.to_compressed());
         transcript.append_message(b"U_2", &self.u_2.to_compressed());
         transcript.append_message(b"R", &self.r_point.to_compressed());
@@ -272,22 +505,17 @@ impl MembershipProofCommitting {
     /// Given the challenge value, compute the s values for Fiat-Shamir and return the actual
     /// proof to be sent to the verifier
     // Follows the second part of the PROVE function on page 88
-    pub fn gen_proof(
-        &self,
-        witness: &Witness,
-        user_id: &UserID,
-        challenge_hash: Element,
-    ) -> MembershipProof {
+    pub fn gen_proof(self, challenge_hash: Element) -> MembershipProof {
         let challenge_hash = challenge_hash.0;
 
-        let s0 = schnorr(self.k[0], witness.secret_key.0, challenge_hash);
+        let s0 = schnorr(self.k[0], self.x, challenge_hash);
         let s1 = schnorr(self.k[1], self.r[0], challenge_hash);
         let s2 = schnorr(self.k[2], self.r[1], challenge_hash);
         let s3 = schnorr(self.k[3], self.r[2], challenge_hash);
-        let s4 = schnorr(self.k[4], self.r[0] * user_id.0, challenge_hash);
-        let s5 = schnorr(self.k[5], self.r[1] * user_id.0, challenge_hash);
-        let s6 = schnorr(self.k[6], self.r[2] * user_id.0, challenge_hash);
-        let s7 = schnorr(self.k[7], user_id.0, challenge_hash);
+        let s4 = schnorr(self.k[4], self.r[0] * self.y, challenge_hash);
+        let s5 = schnorr(self.k[5], self.r[1] * self.y, challenge_hash);
+        let s6 = schnorr(self.k[6], self.r[2] * self.y, challenge_hash);
+        let s7 = schnorr(self.k[7], self.y, challenge_hash);
 
         MembershipProof {
             u_1: self.u_1,
@@ -389,9 +617,31 @@ impl MembershipProof {
         })
     }
 
-    /// Reconstructs t_1, t_2, pi_1, pi_2
+    /// The Fiat-Shamir challenge of the proof
+    pub fn get_challenge(&self) -> Scalar {
+        self.challenge
+    }
+
+    /// The Schnorr response for the user ID, `k_y + c * y`
+    pub fn get_user_id_response(&self) -> Scalar {
+        self.s_7
+    }
+
+    /// The Schnorr response for the user's secret key, `k_0 + c * x`
+    pub fn get_secret_key_response(&self) -> Scalar {
+        self.s_0
+    }
+
+    /// Whether `challenge`, recomputed by the verifier from its transcript,
+    /// is the one the proof was answered with
+    pub fn check_challenge(&self, challenge: Element) -> bool {
+        challenge.0.ct_eq(&self.challenge).into()
+    }
+
+    /// Adds the public inputs to the transcript,
+    /// then reconstructs t_1, t_2, pi_1, pi_2
     /// from the rest of the proof
-    /// Then adds these (and other proof points)
+    /// and adds these (and other proof points)
     /// to the transcript
     pub fn get_bytes_for_challenge(
         &self,
@@ -400,28 +650,41 @@ impl MembershipProof {
         accumulator: &Accumulator,
         transcript: &mut Transcript,
     ) {
-        let t_1 =
-            params.get_x1() * self.s_1 + params.get_y1() * self.s_2 + params.get_z1() * self.s_3
-                - self.r * self.challenge;
-        let t_2 =
-            params.get_x1() * self.s_4 + params.get_y1() * self.s_5 + params.get_z1() * self.s_6
-                - self.r * self.s_7;
-        let pi_1 = pair(
+        PreparedVerifier::new(params, public_keys).get_bytes_for_challenge(
+            self,
+            accumulator,
+            transcript,
+        );
+    }
+
+    /// The G1 points A, B, C, D such that
+    /// Pi_1 = e(A, K) * e(B, Q_m) and Pi_2 = e(C, P) * e(D, Q)
+    fn pairing_bases(&self, params: &AccParams, accumulator: &Accumulator) -> [G1Projective; 4] {
+        [
             params.get_k1() * self.s_0 - self.u_1 * self.s_7
                 + params.get_z1() * self.s_4
                 + params.get_k0() * self.challenge,
-            params.get_k2(),
-        ) + pair(
             params.get_z1() * self.s_1 - self.u_1 * self.challenge,
-            public_keys.sign_key.0,
-        );
-        let pi_2 = pair(
             -self.u_2 * self.s_7 + params.get_z1() * self.s_5 + accumulator.0 * self.challenge,
-            params.get_p2(),
-        ) + pair(
             params.get_z1() * self.s_2 - self.u_2 * self.challenge,
-            public_keys.witness_key.0,
-        );
+        ]
+    }
+
+    /// Reconstructs t_1, t_2 and adds them, the given pi_1, pi_2
+    /// and the other proof points to the transcript
+    fn append_to_transcript(
+        &self,
+        params: &AccParams,
+        pi_1: &Gt,
+        pi_2: &Gt,
+        transcript: &mut Transcript,
+    ) {
+        let t_1 =
+            params.get_x1() * self.s_1 + params.get_y1() * self.s_2 + params.get_z1() * self.s_3
+                - self.r * self.challenge;
+        let t_2 =
+            params.get_x1() * self.s_4 + params.get_y1() * self.s_5 + params.get_z1() * self.s_6
+                - self.r * self.s_7;
         transcript.append_message(b"U_1", self.u_1.to_bytes().as_ref());
         transcript.append_message(b"U_2", self.u_2.to_bytes().as_ref());
         transcript.append_message(b"R", self.r.to_bytes().as_ref());
@@ -431,3 +694,570 @@ impl MembershipProof {
         transcript.append_message(b"Pi_2", pi_2.to_bytes().as_ref());
     }
 }
+
+/// A membership proof together with the prover's pairing commitments
+/// Pi_1 and Pi_2. Sending these lets a verifier check many proofs with
+/// one multi-Miller loop instead of four pairings per proof
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct BatchableMembershipProof {
+    /// The underlying proof, which can also be checked on its own
+    pub proof: MembershipProof,
+    pub(crate) pi_1: Gt,
+    pub(crate) pi_2: Gt,
+}
+
+/// Verifies witnesses and membership proofs against fixed parameters and keys.
+///
+/// The G2 points of every pairing are prepared once when the verifier is
+/// created, so a verifier that checks many proofs should keep one around
+/// instead of calling the static functions on `Witness`
+#[derive(Clone)]
+pub struct PreparedVerifier {
+    params: AccParams,
+    public_keys: PublicKeys,
+    p2: G2Prepared,
+    k2: G2Prepared,
+    witness_key: G2Prepared,
+    sign_key: G2Prepared,
+}
+
+impl fmt::Debug for PreparedVerifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreparedVerifier")
+            .field("params", &self.params)
+            .field("public_keys", &self.public_keys)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PreparedVerifier {
+    /// Prepares the pairing inputs for `params` and `public_keys`
+    pub fn new(params: &AccParams, public_keys: &PublicKeys) -> Self {
+        Self {
+            params: *params,
+            public_keys: *public_keys,
+            p2: G2Prepared::from(params.get_p2().to_affine()),
+            k2: G2Prepared::from(params.get_k2().to_affine()),
+            witness_key: G2Prepared::from(public_keys.witness_key.0.to_affine()),
+            sign_key: G2Prepared::from(public_keys.sign_key.0.to_affine()),
+        }
+    }
+
+    /// The parameters proofs are checked against
+    pub fn params(&self) -> &AccParams {
+        &self.params
+    }
+
+    /// The public keys proofs are checked against
+    pub fn public_keys(&self) -> &PublicKeys {
+        &self.public_keys
+    }
+
+    /// Verifies a witness directly, see `Witness::verify`
+    pub fn verify_witness(
+        &self,
+        accumulator: &Accumulator,
+        y: &UserID,
+        witness: &Witness,
+    ) -> Result<(), &'static str> {
+        // e(W, yP + Q) = e(V, P) is rewritten as e(yW - V, P) * e(W, Q) = 1
+        // so that only the fixed G2 points are paired
+        let first_check = multi_miller_loop(&[
+            (
+                &(witness.witness.0 * y.0 - accumulator.0).to_affine(),
+                &self.p2,
+            ),
+            (&witness.witness.0.to_affine(), &self.witness_key),
+        ])
+        .final_exponentiation()
+        .is_identity();
+        // e(S, yK + Q_m) = e(xK_1 + K_0, K) becomes
+        // e(yS - xK_1 - K_0, K) * e(S, Q_m) = 1
+        let second_check = multi_miller_loop(&[
+            (
+                &(witness.signature * y.0
+                    - self.params.get_k1() * witness.secret_key.0
+                    - self.params.get_k0())
+                .to_affine(),
+                &self.k2,
+            ),
+            (&witness.signature.to_affine(), &self.sign_key),
+        ])
+        .final_exponentiation()
+        .is_identity();
+        if (first_check & second_check).into() {
+            Ok(())
+        } else {
+            Err("failed")
+        }
+    }
+
+    /// Adds the public inputs and the reconstructed proof commitments to
+    /// the transcript, see `MembershipProof::get_bytes_for_challenge`
+    pub fn get_bytes_for_challenge(
+        &self,
+        proof: &MembershipProof,
+        accumulator: &Accumulator,
+        transcript: &mut Transcript,
+    ) {
+        append_public_inputs(&self.params, &self.public_keys, accumulator, transcript);
+        let [a, b, c, d] = proof.pairing_bases(&self.params, accumulator);
+        let pi_1 =
+            multi_miller_loop(&[(&a.to_affine(), &self.k2), (&b.to_affine(), &self.sign_key)])
+                .final_exponentiation();
+        let pi_2 = multi_miller_loop(&[
+            (&c.to_affine(), &self.p2),
+            (&d.to_affine(), &self.witness_key),
+        ])
+        .final_exponentiation();
+        proof.append_to_transcript(&self.params, &pi_1, &pi_2, transcript);
+    }
+
+    /// Verifies a membership proof, see `Witness::check_membership_proof`
+    pub fn check_membership_proof(
+        &self,
+        proof: &MembershipProof,
+        accumulator: &Accumulator,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        let mut transcript = Transcript::new(b"basic_membership_proof");
+        self.get_bytes_for_challenge(proof, accumulator, &mut transcript);
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        proof.check_challenge(challenge)
+    }
+
+    /// Verifies many membership proofs at once,
+    /// see `Witness::batch_check_membership_proofs`
+    pub fn batch_check_membership_proofs(
+        &self,
+        proofs: &[(BatchableMembershipProof, [u8; 2 * SECURITY_BYTES])],
+        accumulator: &Accumulator,
+    ) -> Result<(), Vec<usize>> {
+        self.batch_check_membership_proofs_with_rng(proofs, accumulator, rand::rngs::OsRng)
+    }
+
+    /// Like `batch_check_membership_proofs`, drawing the random weights from `rng`
+    pub fn batch_check_membership_proofs_with_rng(
+        &self,
+        proofs: &[(BatchableMembershipProof, [u8; 2 * SECURITY_BYTES])],
+        accumulator: &Accumulator,
+        mut rng: impl RngCore + CryptoRng,
+    ) -> Result<(), Vec<usize>> {
+        if self.batch_check(proofs, accumulator, &mut rng) {
+            return Ok(());
+        }
+        Err(proofs
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !self.batch_check(std::slice::from_ref(*p), accumulator, &mut rng))
+            .map(|(i, _)| i)
+            .collect())
+    }
+
+    /// Checks every transcript, then the random linear combination
+    ///
+    /// sum(rho_j Pi_1j + sigma_j Pi_2j) = e(sum(rho_j A_j), K) * e(sum(rho_j B_j), Q_m)
+    ///                                  * e(sum(sigma_j C_j), P) * e(sum(sigma_j D_j), Q)
+    fn batch_check(
+        &self,
+        proofs: &[(BatchableMembershipProof, [u8; 2 * SECURITY_BYTES])],
+        accumulator: &Accumulator,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> bool {
+        let mut prefix = Transcript::new(b"basic_membership_proof");
+        append_public_inputs(&self.params, &self.public_keys, accumulator, &mut prefix);
+
+        let mut pi = Gt::IDENTITY;
+        let mut a = G1Projective::IDENTITY;
+        let mut b = G1Projective::IDENTITY;
+        let mut c = G1Projective::IDENTITY;
+        let mut d = G1Projective::IDENTITY;
+        for (proof, ephemeral_challenge) in proofs {
+            let mut transcript = prefix.clone();
+            proof.proof.append_to_transcript(
+                &self.params,
+                &proof.pi_1,
+                &proof.pi_2,
+                &mut transcript,
+            );
+            transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+            let challenge = Element::from_transcript(b"challenge", &mut transcript);
+            if !proof.proof.check_challenge(challenge) {
+                return false;
+            }
+
+            let rho = random_u128(&mut *rng);
+            let sigma = random_u128(&mut *rng);
+            let [a_j, b_j, c_j, d_j] = proof.proof.pairing_bases(&self.params, accumulator);
+            pi += gt_mul_u128(&proof.pi_1, rho) + gt_mul_u128(&proof.pi_2, sigma);
+            a += a_j * Scalar::from(rho);
+            b += b_j * Scalar::from(rho);
+            c += c_j * Scalar::from(sigma);
+            d += d_j * Scalar::from(sigma);
+        }
+
+        let rhs = multi_miller_loop(&[
+            (&a.to_affine(), &self.k2),
+            (&b.to_affine(), &self.sign_key),
+            (&c.to_affine(), &self.p2),
+            (&d.to_affine(), &self.witness_key),
+        ])
+        .final_exponentiation();
+        pi == rhs
+    }
+}
+
+/// Commits to the public parameters and keys a membership proof is made against
+fn append_public_inputs(
+    params: &AccParams,
+    public_keys: &PublicKeys,
+    accumulator: &Accumulator,
+    transcript: &mut Transcript,
+) {
+    transcript.append_message(
+        b"Signature Public Key",
+        public_keys.witness_key.to_bytes().as_ref(),
+    );
+    transcript.append_message(
+        b"Witness Public Key",
+        public_keys.sign_key.to_bytes().as_ref(),
+    );
+    transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
+    params.add_to_transcript(transcript);
+}
+
+/// A membership proof together with the accumulator and epoch it was made against
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct EpochMembershipProof {
+    /// The id of the accumulator, see `PublicKeys::accumulator_id`
+    pub accumulator_id: AccumulatorId,
+    /// The epoch of the accumulator
+    pub epoch: usize,
+    /// The proof, whose transcript also commits to the id and epoch
+    pub proof: MembershipProof,
+}
+
+/// A request for a current membership witness for `id`, proving possession
+/// of the long-term secret key x and signature R without revealing them.
+///
+/// R is blinded as R' = r * R, and the request proves knowledge of r and
+/// r * x such that R' * (s_m + y) = r * x * K1 + r * K0. Only the server
+/// holding the signing secret key s_m can check it
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct ReissueRequest {
+    /// The user ID to re-issue a witness for
+    pub id: UserID,
+    /// The blinded signature R'
+    pub blinded_signature: G1Projective,
+    /// The Fiat-Shamir challenge
+    pub challenge: Element,
+    /// The response for r * x
+    pub key_response: Element,
+    /// The response for r
+    pub blinding_response: Element,
+}
+
+impl ReissueRequest {
+    /// Creates a request to re-issue the membership witness of `witness`
+    pub fn new(
+        witness: &Witness,
+        id: &UserID,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+    ) -> Self {
+        Self::new_with_rng(witness, id, params, public_keys, &mut rand::rngs::OsRng)
+    }
+
+    /// Like `new`, drawing the blinding factor and proof nonces from `rng`
+    pub fn new_with_rng(
+        witness: &Witness,
+        id: &UserID,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Self {
+        let r = Element::random_with_rng(&mut *rng);
+        let rx = r.0 * witness.secret_key.0;
+        let blinded_signature = witness.signature * r.0;
+
+        let k_rx = Element::random_with_rng(&mut *rng);
+        let k_r = Element::random_with_rng(&mut *rng);
+        let commitment = params.get_k1() * k_rx.0 + params.get_k0() * k_r.0;
+        let challenge = Self::challenge(id, public_keys, &blinded_signature, &commitment);
+        Self {
+            id: *id,
+            blinded_signature,
+            challenge,
+            key_response: Element(k_rx.0 - challenge.0 * rx),
+            blinding_response: Element(k_r.0 - challenge.0 * r.0),
+        }
+    }
+
+    /// Verifies the request with the signing secret key s_m
+    pub fn verify(
+        &self,
+        params: &AccParams,
+        public_keys: &PublicKeys,
+        sign_secret_key: &SecretKey,
+    ) -> Result<(), &'static str> {
+        if bool::from(self.blinded_signature.is_identity()) {
+            return Err("invalid re-issue request");
+        }
+        // R' * (s_m + y) = r * x * K1 + r * K0
+        let unblinded = self.blinded_signature * (sign_secret_key.0 + self.id.0);
+        let commitment = params.get_k1() * self.key_response.0
+            + params.get_k0() * self.blinding_response.0
+            + unblinded * self.challenge.0;
+        let challenge =
+            Self::challenge(&self.id, public_keys, &self.blinded_signature, &commitment);
+        if bool::from(challenge.0.ct_eq(&self.challenge.0)) {
+            Ok(())
+        } else {
+            Err("invalid re-issue request")
+        }
+    }
+
+    fn challenge(
+        id: &UserID,
+        public_keys: &PublicKeys,
+        blinded_signature: &G1Projective,
+        commitment: &G1Projective,
+    ) -> Element {
+        let mut transcript = Transcript::new(b"user_reissue_proof");
+        transcript.append_message(b"User ID", &id.0.to_le_bytes());
+        transcript.append_message(b"Accumulator ID", &public_keys.accumulator_id());
+        transcript.append_message(b"Blinded signature", blinded_signature.to_bytes().as_ref());
+        transcript.append_message(b"commitment", commitment.to_bytes().as_ref());
+        Element::from_transcript(b"challenge", &mut transcript)
+    }
+}
+
+/// The commitment phase of a proof that a user ID is not in a server's
+/// revoked-set accumulator, mirroring `MembershipProofCommitting`.
+///
+/// Giving the user ID a `ProofMessage::SharedBlinding` and answering this
+/// and a membership proof with one challenge from a shared transcript
+/// shows that both proofs hide the same user ID
+pub struct NonMembershipProofCommitting {
+    committing: accumulator::NonMembershipProofCommitting,
+    accumulator: Accumulator,
+    public_key: PublicKey,
+    proof_params: ProofParams,
+}
+
+impl fmt::Debug for NonMembershipProofCommitting {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "NonMembershipProofCommitting {{ accumulator: {}, public_key: {}, <secrets redacted> }}",
+            self.accumulator, self.public_key
+        )
+    }
+}
+
+impl NonMembershipProofCommitting {
+    /// Create a new non-membership proof committing phase.
+    /// Returns `None` if the witness is not valid for the hidden user ID
+    pub fn new(
+        user_id: ProofMessage,
+        witness: &NonMembershipWitness,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+    ) -> Option<Self> {
+        Self::new_with_rng(user_id, witness, accumulator, public_key, rand::rngs::OsRng)
+    }
+
+    /// Like `new`, drawing the blinding factors from `rng`
+    pub fn new_with_rng(
+        user_id: ProofMessage,
+        witness: &NonMembershipWitness,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+        mut rng: impl RngCore + CryptoRng,
+    ) -> Option<Self> {
+        let y = Element(user_id.get_message());
+        if !witness.verify(y, *public_key, *accumulator) {
+            return None;
+        }
+        let blinder = Element(user_id.get_blinder(&mut rng));
+        let proof_params = ProofParams::new(*public_key, None);
+        let committing = accumulator::NonMembershipProofCommitting::new_with_rng(
+            y,
+            *witness,
+            proof_params,
+            *public_key,
+            Some(blinder),
+            rng,
+        );
+        Some(Self {
+            committing,
+            accumulator: *accumulator,
+            public_key: *public_key,
+            proof_params,
+        })
+    }
+
+    /// Adds the public inputs and the commitments to `transcript`
+    pub fn get_bytes_for_challenge(&self, transcript: &mut Transcript) {
+        append_revoked_set_inputs(
+            &self.accumulator,
+            &self.public_key,
+            &self.proof_params,
+            transcript,
+        );
+        self.committing.get_bytes_for_challenge(transcript);
+    }
+
+    /// Given the challenge value, computes the responses.
+    ///
+    /// Consumes the commitment, since answering two challenges with the
+    /// same blinding factors would reveal the user ID
+    pub fn gen_proof(self, challenge_hash: Element) -> NonMembershipProof {
+        NonMembershipProof {
+            challenge: challenge_hash.0,
+            proof: self.committing.gen_proof(challenge_hash),
+        }
+    }
+}
+
+/// A ZKP that a user ID is not in a server's revoked-set accumulator
+#[derive(Debug, Copy, Clone, Deserialize, Serialize)]
+pub struct NonMembershipProof {
+    pub(crate) challenge: Scalar,
+    pub(crate) proof: accumulator::NonMembershipProof,
+}
+
+impl NonMembershipProof {
+    /// Proves that `user_id` is not in the revoked set accumulated in `accumulator`.
+    /// Returns `None` if the witness is not valid
+    pub fn new(
+        user_id: &UserID,
+        witness: &NonMembershipWitness,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> Option<Self> {
+        Self::new_with_rng(
+            user_id,
+            witness,
+            accumulator,
+            public_key,
+            ephemeral_challenge,
+            rand::rngs::OsRng,
+        )
+    }
+
+    /// Like `new`, drawing the blinding factors from `rng`
+    pub fn new_with_rng(
+        user_id: &UserID,
+        witness: &NonMembershipWitness,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+        rng: impl RngCore + CryptoRng,
+    ) -> Option<Self> {
+        let committing = NonMembershipProofCommitting::new_with_rng(
+            ProofMessage::Hidden { message: user_id.0 },
+            witness,
+            accumulator,
+            public_key,
+            rng,
+        )?;
+        let mut transcript = Transcript::new(b"non_membership_proof");
+        committing.get_bytes_for_challenge(&mut transcript);
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        Some(committing.gen_proof(challenge))
+    }
+
+    /// Verifies the proof against the revoked-set accumulator and its public key
+    pub fn verify(
+        &self,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+        ephemeral_challenge: &[u8; 2 * SECURITY_BYTES],
+    ) -> bool {
+        let mut transcript = Transcript::new(b"non_membership_proof");
+        self.get_bytes_for_challenge(accumulator, public_key, &mut transcript);
+        transcript.append_message(b"Ephemeral challenge", ephemeral_challenge);
+        let challenge = Element::from_transcript(b"challenge", &mut transcript);
+        self.check_challenge(challenge)
+    }
+
+    /// Adds the public inputs to the transcript, then reconstructs the
+    /// commitments from the rest of the proof and adds them as well
+    pub fn get_bytes_for_challenge(
+        &self,
+        accumulator: &Accumulator,
+        public_key: &PublicKey,
+        transcript: &mut Transcript,
+    ) {
+        let proof_params = ProofParams::new(*public_key, None);
+        append_revoked_set_inputs(accumulator, public_key, &proof_params, transcript);
+        self.proof
+            .finalize(
+                *accumulator,
+                proof_params,
+                *public_key,
+                Element(self.challenge),
+            )
+            .get_bytes_for_challenge(transcript);
+    }
+
+    /// The challenge the proof was answered with
+    pub fn get_challenge(&self) -> Scalar {
+        self.challenge
+    }
+
+    /// The response for the user ID, which equals that of any proof
+    /// answered with the same challenge that hides the same user ID
+    /// with the same shared blinding
+    pub fn get_user_id_response(&self) -> Scalar {
+        self.proof.get_s_y()
+    }
+
+    /// Checks the proof's challenge against a challenge that was
+    /// recomputed from the transcript
+    pub fn check_challenge(&self, challenge: Element) -> bool {
+        challenge.0.ct_eq(&self.challenge).into()
+    }
+}
+
+/// Adds the revoked set's public parameters and keys to the transcript
+fn append_revoked_set_inputs(
+    accumulator: &Accumulator,
+    public_key: &PublicKey,
+    proof_params: &ProofParams,
+    transcript: &mut Transcript,
+) {
+    transcript.append_message(b"Revoked Set Public Key", public_key.to_bytes().as_ref());
+    transcript.append_message(b"Accumulator", accumulator.to_bytes().as_ref());
+    proof_params.add_to_transcript(transcript);
+}
+
+/// Binds a proof to an accumulator and epoch
+fn append_epoch(accumulator_id: &AccumulatorId, epoch: usize, transcript: &mut Transcript) {
+    transcript.append_message(b"Accumulator ID", accumulator_id);
+    transcript.append_u64(b"Epoch", epoch as u64);
+}
+
+/// A random 128-bit weight for batch verification
+fn random_u128(rng: &mut impl RngCore) -> u128 {
+    let mut bytes = [0u8; 16];
+    rng.fill_bytes(&mut bytes);
+    u128::from_le_bytes(bytes)
+}
+
+/// Multiplies a target group element by a 128-bit weight, which is
+/// about half the work of multiplying by a full scalar
+fn gt_mul_u128(point: &Gt, weight: u128) -> Gt {
+    let mut res = Gt::IDENTITY;
+    for i in (0..u128::BITS - weight.leading_zeros()).rev() {
+        res = res.double();
+        if (weight >> i) & 1 == 1 {
+            res += point;
+        }
+    }
+    res
+}
@@ -3,6 +3,8 @@ from fastapi import FastAPI, HTTPException, Request
 import importlib.util
 import base64
 import ctypes
+import os
+import time
 # from .models import *  # this line if you are running the code in docker
 from models import * # this line if you are running the code locally
 
@@ -13,9 +15,17 @@ spec = importlib.util.spec_from_file_location("bindings", path)
 bindings = importlib.util.module_from_spec(spec)
 spec.loader.exec_module(bindings)
 
+# how many of the latest accumulators an epoch membership proof may be made against
+proof_window = int(os.getenv("PROOF_WINDOW", "1"))
+# how long an issued challenge stays valid, in seconds
+challenge_ttl = int(os.getenv("CHALLENGE_TTL", "300"))
+# how many challenges the verifier tracks per challenge_ttl seconds
+challenge_capacity = int(os.getenv("CHALLENGE_CAPACITY", "10000"))
+
 @asynccontextmanager
 async def lifespan(app: FastAPI):
     app.state.registry = bindings.new_server()
+    app.state.verifier = bindings.new_verifier_session(os.urandom(32), challenge_ttl, challenge_capacity)
     print(f"Server started: {app.state.registry}")
     yield
     print("Server shutting down")
@@ -25,6 +35,9 @@ app = FastAPI(lifespan=lifespan)
 def get_registry_state():
     return app.state.registry
 
+def get_verifier_session():
+    return app.state.verifier
+
 @app.get("/server")
 def get_server():
     # return {"registry": f"{get_registry_state()}"}
@@ -87,6 +100,42 @@ def user_create_witness(user_input: UserInput):
     except Exception as e:
         raise HTTPException(status_code=400, detail=str(e))
     
+# the holder makes the request with bindings.user_make_reissue_request and applies
+# the response with bindings.user_apply_reissued_witness, so its secret key never leaves it
+@app.post("/user_reissue_witness")
+def user_reissue_witness(reissue_input: ReissueInput):
+    try:
+        request = base64.b64decode(reissue_input.request)
+        server = get_registry_state()
+        reissued = bindings.user_reissue_witness(server, request)
+        encoded_reissued = base64.b64encode(reissued).decode('utf-8')
+        return {"reissued": encoded_reissued}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    
+@app.post("/server_rotate_keys")
+def server_rotate_keys(grace_until: int):
+    try:
+        server = get_registry_state()
+        linkage = bindings.server_rotate_keys(server, grace_until)
+        encoded_linkage = base64.b64encode(linkage).decode('utf-8')
+        return {"linkage": encoded_linkage}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+# the holder makes the request with bindings.user_make_migration_request and applies
+# the response with bindings.user_apply_migrated_witness, so its secret key never leaves it
+@app.post("/user_migrate_witness")
+def user_migrate_witness(migration_input: MigrationInput):
+    try:
+        request = base64.b64decode(migration_input.request)
+        server = get_registry_state()
+        migrated = bindings.user_migrate_witness(server, request)
+        encoded_migrated = base64.b64encode(migrated).decode('utf-8')
+        return {"migrated": encoded_migrated}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    
 @app.post("/user_check_witness")
 def user_check_witness(user_input: UserInput):
     try:
@@ -97,13 +146,23 @@ def user_check_witness(user_input: UserInput):
     except Exception as e:
         raise HTTPException(status_code=400, detail=str(e))
     
+@app.get("/verifier_challenge")
+def verifier_challenge():
+    try:
+        session = get_verifier_session()
+        challenge = bindings.verifier_issue_challenge(session, int(time.time()))
+        encoded_challenge = base64.b64encode(challenge).decode('utf-8')
+        return {"challenge": encoded_challenge}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
 @app.post("/user_make_membership_proof")
-def user_make_membership_proof(user_input: UserInput):
+def user_make_membership_proof(challenge_input: ChallengeInput):
     try:
-        encoded_user_str = user_input.user
-        user = base64.b64decode(encoded_user_str)
+        user = base64.b64decode(challenge_input.user)
+        challenge = base64.b64decode(challenge_input.challenge)
         server = get_registry_state()
-        membership_proof = bindings.user_make_membership_proof(server, user)
+        membership_proof = bindings.user_make_membership_proof(server, user, challenge)
         encoded_membership_proof = base64.b64encode(membership_proof).decode('utf-8')
         return {"Membership proof is": encoded_membership_proof}
     except Exception as e:
@@ -115,11 +174,138 @@ def witness_check_membership_proof(proof_input: ProofInput):
         encoded_proof_str = proof_input.proof
         proof = base64.b64decode(encoded_proof_str)
         server = get_registry_state()
-        bindings.witness_check_membership_proof(server, proof)
+        session = get_verifier_session()
+        bindings.verifier_check_membership_proof(session, server, proof, int(time.time()))
         return {"Membership proof verified successfully"}
     except Exception as e:
         raise HTTPException(status_code=400, detail=str(e))
     
+@app.post("/user_make_epoch_membership_proof")
+def user_make_epoch_membership_proof(challenge_input: ChallengeInput):
+    try:
+        user = base64.b64decode(challenge_input.user)
+        challenge = base64.b64decode(challenge_input.challenge)
+        server = get_registry_state()
+        membership_proof = bindings.user_make_epoch_membership_proof(server, user, challenge)
+        encoded_membership_proof = base64.b64encode(membership_proof).decode('utf-8')
+        return {"Membership proof is": encoded_membership_proof}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/server_check_epoch_membership_proof")
+def server_check_epoch_membership_proof(proof_input: ProofInput):
+    try:
+        encoded_proof_str = proof_input.proof
+        proof = base64.b64decode(encoded_proof_str)
+        server = get_registry_state()
+        public_keys = bindings.server_get_public_keys(server)
+        history = bindings.server_publish_accumulator_history(server, proof_window)
+        session = get_verifier_session()
+        bindings.verifier_check_epoch_membership_proof(session, public_keys, history, proof, proof_window, int(time.time()))
+        return {"Membership proof verified successfully"}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.get("/server_publish")
+def server_publish(registry_id: str):
+    try:
+        server = get_registry_state()
+        publication = bindings.server_publish(server, registry_id)
+        encoded_publication = base64.b64encode(publication).decode('utf-8')
+        return {"publication": encoded_publication}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/user_make_non_revocation_presentation")
+def user_make_non_revocation_presentation(request: PresentationRequest):
+    try:
+        user = base64.b64decode(request.user)
+        challenge = base64.b64decode(request.challenge)
+        server = get_registry_state()
+        presentation = bindings.user_make_non_revocation_presentation(server, user, request.registry_id, challenge)
+        encoded_presentation = base64.b64encode(presentation).decode('utf-8')
+        return {"presentation": encoded_presentation}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/check_non_revocation_presentation")
+def check_non_revocation_presentation(presentation_input: PresentationInput):
+    try:
+        presentation = base64.b64decode(presentation_input.presentation)
+        server = get_registry_state()
+        publication = bindings.server_publish(server, presentation_input.registry_id)
+        session = get_verifier_session()
+        bindings.check_non_revocation_presentation(session, presentation, publication, int(time.time()))
+        return {"Presentation verified successfully"}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/user_create_non_membership_witness")
+def user_create_non_membership_witness(user_input: UserInput):
+    try:
+        encoded_user_str = user_input.user
+        user = base64.b64decode(encoded_user_str)
+        server = get_registry_state()
+        user = bindings.user_create_non_membership_witness(server, user)
+        encoded_user = base64.b64encode(user).decode('utf-8')
+        return {"user": encoded_user}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.get("/server_revoked_set_updates")
+def server_revoked_set_updates(epoch: int = 1):
+    try:
+        server = get_registry_state()
+        updates = bindings.server_publish_revoked_set_updates(server, epoch)
+        encoded_updates = base64.b64encode(updates).decode('utf-8')
+        return {"epoch": epoch, "updates": encoded_updates}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/server_prune")
+def server_prune(retain_epochs: int):
+    try:
+        server = get_registry_state()
+        checkpoint = bindings.server_prune(server, retain_epochs)
+        encoded_checkpoint = base64.b64encode(checkpoint).decode('utf-8')
+        return {"checkpoint": encoded_checkpoint}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/user_apply_revoked_set_updates")
+def user_apply_revoked_set_updates(update_input: RevokedSetUpdateInput):
+    try:
+        user = base64.b64decode(update_input.user)
+        updates = base64.b64decode(update_input.updates)
+        user = bindings.user_apply_revoked_set_updates(user, updates)
+        encoded_user = base64.b64encode(user).decode('utf-8')
+        return {"user": encoded_user}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/user_make_non_membership_proof")
+def user_make_non_membership_proof(challenge_input: ChallengeInput):
+    try:
+        user = base64.b64decode(challenge_input.user)
+        challenge = base64.b64decode(challenge_input.challenge)
+        proof = bindings.user_make_non_membership_proof(user, challenge)
+        encoded_proof = base64.b64encode(proof).decode('utf-8')
+        return {"Non-membership proof is": encoded_proof}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
+@app.post("/server_check_non_membership_proof")
+def server_check_non_membership_proof(proof_input: ProofInput):
+    try:
+        encoded_proof_str = proof_input.proof
+        proof = base64.b64decode(encoded_proof_str)
+        server = get_registry_state()
+        session = get_verifier_session()
+        bindings.server_check_non_membership_proof(session, server, proof, int(time.time()))
+        return {"Non-membership proof verified successfully"}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
 @app.post("/user_mpc_update")
 def user_update(update_input: UpdateInput):
     try:
@@ -138,6 +324,16 @@ def server_get_epoch():
     epoch = bindings.server_get_epoch(server)
     return {"Current epoch is": epoch}
 
+@app.get("/server_split_updates")
+def server_split_updates(epoch: int = 1, slice_size: int = 50):
+    try:
+        server = get_registry_state()
+        updates = bindings.server_publish_split_updates(server, epoch, slice_size)
+        encoded_updates = base64.b64encode(updates).decode('utf-8')
+        return {"epoch": epoch, "updates": encoded_updates}
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+
 @app.get("/server_get_accumulator")
 def server_get_accumulator():
     server = get_registry_state()
@@ -6,9 +6,32 @@ class UserInput(BaseModel):
 class ProofInput(BaseModel):
     proof: str
 
+class ChallengeInput(BaseModel):
+    user: str
+    challenge: str
+
+class ReissueInput(BaseModel):
+    request: str
+
+class MigrationInput(BaseModel):
+    request: str
+
 class UserList(BaseModel):
     users: list[str]
 
 class UpdateInput(BaseModel):
     user: str
-    threshold: int
\ No newline at end of file
+    threshold: int
+
+class RevokedSetUpdateInput(BaseModel):
+    user: str
+    updates: str
+
+class PresentationRequest(BaseModel):
+    user: str
+    registry_id: str
+    challenge: str
+
+class PresentationInput(BaseModel):
+    presentation: str
+    registry_id: str
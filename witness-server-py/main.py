@@ -1,12 +1,9 @@
-from contextlib import asynccontextmanager
-from hashlib import shake_128
-from fastapi import FastAPI, HTTPException, Request
+from fastapi import FastAPI, HTTPException
 from fastapi.responses import JSONResponse
 from datetime import datetime
 import importlib.util
 import asyncio
 import os
-import json
 import requests
 import base64
 
@@ -20,40 +17,39 @@ spec = importlib.util.spec_from_file_location("bindings", path)
 bindings = importlib.util.module_from_spec(spec)
 spec.loader.exec_module(bindings)
 
-witness_cache = {}
-
-# sample revocation file format
-tmp_revocation_file = {
-    "timestamp": 1630000000,
-    "revoked":[
-        {
-            "deletion": "element_delete",
-            "coefficient": "element_coefficient"
-        },
-        {
-            "deletion": "element_delete2",
-            "coefficient": "element_coefficient2"
-        }
-    ]
-}
-
-async def update_cache_periodically():
+# the registry endpoint publishing single-server split updates
+revocation_url = os.getenv("REVOCATION_URL", "http://localhost:8000/server_split_updates")
+# the registry endpoint publishing its accumulator and public keys
+publication_url = os.getenv("PUBLICATION_URL", "http://localhost:8000/server_publish")
+registry_id = os.getenv("REGISTRY_ID", "default")
+# how many epochs of updates to keep for registering older witnesses,
+# should match the registry's retention
+retain_epochs = int(os.getenv("RETAIN_EPOCHS", "1000"))
+
+# tracks holder witnesses and applies the published updates to them,
+# checking them against the registry's published public keys
+def new_witness_service():
+    response = requests.get(publication_url, params={"registry_id": registry_id})
+    response.raise_for_status()
+    publication = base64.b64decode(response.json()["publication"])
+    return bindings.new_witness_service(publication, retain_epochs)
+
+witness_service = new_witness_service()
+
+async def update_service_periodically():
     try:
         while True:
-            current_time = datetime.now().timestamp()
-            for user_id, data in witness_cache.items():
-                updated_witness = allosaurus_multi_batch_update(user_id, data["witness"], data["timestamp"])
-                witness_cache[user_id] = {
-                    "witness": updated_witness,
-                    "timestamp": current_time
-                }
-            print("Cache updated at: ", current_time)
-            await asyncio.sleep(3600) # update cache every hour
+            try:
+                ingest_updates()
+                print("Updates ingested at: ", datetime.now().timestamp())
+            except HTTPException as e:
+                print("Failed to ingest updates: ", e.detail)
+            await asyncio.sleep(3600) # fetch updates every hour
     except asyncio.CancelledError:
-        print("Cache update task cancelled")
+        print("Update task cancelled")
 
 async def app_lifespan(app: FastAPI):
-    task = asyncio.create_task(update_cache_periodically())
+    task = asyncio.create_task(update_service_periodically())
     yield
     task.cancel()
     await task
@@ -61,87 +57,37 @@ async def app_lifespan(app: FastAPI):
 app = FastAPI(lifespan=app_lifespan)
 
 
-# function to get the all deltas from a file given given current witness and current timestamp?
-def allosaurus_multi_batch_update(user_id, witness, timestamp, reovcation_file_path):
-
-    revocation_json = get_revocation_file(reovcation_file_path)
-    revocation_file = json.loads(revocation_json)
-
-    file_timestamp = revocation_file["timestamp"]
-    revoked_list = revocation_file["revoked"]
-
-    # check if the file is newer than the current timestamp
-    # if it is not, return the witness
-    if file_timestamp <= timestamp:
-        return witness
-    
-    # if it is, update the witness
-    all_deletions = [r["deletion"] for r in revoked_list]
-    all_coefficients = [r["coefficient"] for r in revoked_list]
-
-    # convert deletions to guid
-    guids_list = [generate_hash(ikm=deletion.encode()) for deletion in all_deletions]
-
-    # if the user is in the revoked list, return None
-    if user_id in guids_list:
-        return None
-
-    # Uncomment the following code to update the witness
-    # try:
-    #     new_witness = bindings.multi_batch_update(witness, all_deletions, all_coefficients)
-    #     encoded_witness = base64.b64encode(new_witness).decode('utf-8')
-    #     return {"witness": encoded_witness, "timestamp": datetime.now().timestamp()}
-    # except Exception as e:
-    #     raise HTTPException(status_code=400, detail=str(e))
-
-
-# function to retrieve the revocation file
-def get_revocation_file(file_path):
+# fetch every update after the service's epoch from the registry and apply it
+def ingest_updates():
     try:
-        response = requests.get(file_path)
+        epoch = bindings.witness_service_get_epoch(witness_service)
+        response = requests.get(revocation_url, params={"epoch": epoch})
         response.raise_for_status()
-        return response.json()
+        updates = base64.b64decode(response.json()["updates"])
+        return bindings.witness_service_ingest(witness_service, updates)
     except Exception as e:
         raise HTTPException(status_code=400, detail=str(e))
 
-# function to generate a hash same as Rust. 
-# q = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001
-def generate_hash(salt=b"VB-ACC-HASH-SALT-", ikm=None): 
-    q = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001 
-    shake = shake_128()
-    shake.update(salt)
-    if ikm is not None:
-        shake.update(ikm)
-    else:
-        random_bytes = os.urandom(32)
-        shake.update(random_bytes)
-    hash_bytes = shake.digest(64)
-    hash_int = int.from_bytes(hash_bytes, byteorder='little') % q
-    result = hex(hash_int)
-    return result
-
 
 @app.get("/")
 def hello_world():
     return {"Hello": "World"}
 
 
-# holder to check the deltas from the file
+# holder to register their witness and fetch it for the latest epoch
 @app.post("/holder_witness_update")
 def holder_witness_update(WitnessUpdateInput: WitnessUpdateInput):
-    user_guid = WitnessUpdateInput.user_guid
-    current_witness = WitnessUpdateInput.current_witness
-    current_timestamp = WitnessUpdateInput.current_timestamp
-    revocation_file_path = WitnessUpdateInput.revocation_file_url
-
-    if user_guid not in witness_cache:
-        updated_witness = allosaurus_multi_batch_update(user_guid, current_witness, current_timestamp, revocation_file_path)
-        witness_cache[user_guid] = {
-            "witness": updated_witness["witness"],
-            "timestamp": updated_witness["timestamp"]
-        }
-    
-    if witness_cache[user_guid]["witness"] is None:
-        return JSONResponse(status_code=400, content={"message": "User has been revoked"})
-
-    return witness_cache[user_guid]
\ No newline at end of file
+    user_id = base64.b64decode(WitnessUpdateInput.user_id)
+    current_witness = base64.b64decode(WitnessUpdateInput.current_witness)
+
+    ingest_updates()
+    try:
+        bindings.witness_service_register(witness_service, user_id, current_witness, WitnessUpdateInput.current_epoch)
+        witness = bindings.witness_service_get_witness(witness_service, user_id)
+    except Exception as e:
+        return JSONResponse(status_code=400, content={"message": str(e)})
+
+    return {
+        "witness": base64.b64encode(witness).decode('utf-8'),
+        "epoch": bindings.witness_service_get_epoch(witness_service),
+    }
@@ -1,10 +1,9 @@
 from pydantic import BaseModel
 
 class WitnessUpdateInput(BaseModel):
-    user_guid: str
+    user_id: str # base64 encoded
     current_witness: str # base64 encoded
-    current_timestamp: int
-    revocation_file_url: str
+    current_epoch: int
 
 class IssuerInput(BaseModel):
     user_guid: str